@@ -0,0 +1,37 @@
+//! Contract bindings shared by the binaries.
+
+use ethers::contract::abigen;
+
+// ERC20 ABI (simplified)
+abigen!(
+    IERC20,
+    r#"[
+        function balanceOf(address account) external view returns (uint256)
+        function transfer(address to, uint256 amount) external returns (bool)
+        function symbol() external view returns (string)
+        function decimals() external view returns (uint8)
+    ]"#,
+);
+
+// FlashLoanTester ABI
+abigen!(
+    IFlashLoanTester,
+    r#"[
+        function owner() external view returns (address)
+        function flashLoanProvider() external view returns (address)
+        function testFlashLoan(address token, uint256 amount, uint8 mode) external
+        function executeFlashLoanBatch(address[] tokens, uint256[] amounts, uint8 mode, bytes params) external
+        function withdraw(address token, uint256 amount) external
+        function transferOwnership(address newOwner) external
+    ]"#,
+);
+
+// Flash loan provider ABI. `supportedTokens` is optional; check the code
+// for its selector before relying on it.
+abigen!(
+    IFlashLoanProvider,
+    r#"[
+        function flashloan(address token, uint256 amount, bytes params) external
+        function supportedTokens() external view returns (address[])
+    ]"#,
+);
@@ -0,0 +1,134 @@
+//! EIP-2930 access lists for outgoing transactions.
+//!
+//! Declaring the accounts and storage slots a transaction touches makes
+//! those reads cheaper, which can pay off for a callback that does a lot
+//! of work. It doesn't always: every listed entry costs gas up front. So
+//! [`generate_access_list`] asks the node for a list with
+//! `eth_createAccessList` and only recommends it when the estimate with the
+//! list is lower than without.
+
+use ethers::{
+    providers::Middleware,
+    types::{
+        transaction::{eip2718::TypedTransaction, eip2930::AccessList},
+        U256,
+    },
+};
+
+/// Why a legacy transaction is sent without its access list.
+pub const LEGACY_NO_ACCESS_LIST: &str = "legacy transactions can't carry an access list";
+
+/// Attach `list` to `tx`, returning whether it was attached.
+///
+/// Legacy transactions can't carry an access list. They are left as they
+/// are rather than turned into EIP-2930 transactions behind the back of a
+/// `tx_type = "legacy"` config.
+pub fn with_access_list(tx: &mut TypedTransaction, list: AccessList) -> bool {
+    if matches!(tx, TypedTransaction::Legacy(_)) {
+        return false;
+    }
+    tx.set_access_list(list);
+    true
+}
+
+/// What [`generate_access_list`] found.
+#[derive(Debug, Clone)]
+pub enum AccessListCheck {
+    /// `list` lowers the gas estimate from `without` to `with`.
+    Saves {
+        list: AccessList,
+        without: U256,
+        with: U256,
+    },
+    /// The generated list doesn't lower the estimate.
+    NoSavings { without: U256, with: U256 },
+    /// No list could be generated, e.g. because the node doesn't serve
+    /// `eth_createAccessList`.
+    Unavailable(String),
+}
+
+/// Generate an access list for `tx` and compare estimates with and without it.
+///
+/// `client` should be the signer that will send `tx`. Nothing here fails:
+/// when any of the calls does, or `tx` is a legacy transaction, the result
+/// is [`AccessListCheck::Unavailable`] and `tx` can be sent as it is.
+pub async fn generate_access_list<M: Middleware>(
+    client: &M,
+    tx: &TypedTransaction,
+) -> AccessListCheck {
+    if matches!(tx, TypedTransaction::Legacy(_)) {
+        return AccessListCheck::Unavailable(LEGACY_NO_ACCESS_LIST.into());
+    }
+    let generated = match client.create_access_list(tx, None).await {
+        Ok(generated) => generated,
+        Err(e) => return AccessListCheck::Unavailable(e.to_string()),
+    };
+    let without = match client.estimate_gas(tx, None).await {
+        Ok(gas) => gas,
+        Err(e) => return AccessListCheck::Unavailable(e.to_string()),
+    };
+
+    let mut listed = tx.clone();
+    with_access_list(&mut listed, generated.access_list.clone());
+    let with = match client.estimate_gas(&listed, None).await {
+        Ok(gas) => gas,
+        Err(e) => return AccessListCheck::Unavailable(e.to_string()),
+    };
+
+    if with < without {
+        AccessListCheck::Saves {
+            list: generated.access_list,
+            without,
+            with,
+        }
+    } else {
+        AccessListCheck::NoSavings { without, with }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::dead_endpoint;
+    use ethers::{
+        providers::{Http, Provider},
+        types::{
+            transaction::eip2930::AccessListItem, Address, Eip1559TransactionRequest,
+            TransactionRequest,
+        },
+    };
+
+    fn list() -> AccessList {
+        AccessList(vec![AccessListItem {
+            address: Address::repeat_byte(0x11),
+            storage_keys: Vec::new(),
+        }])
+    }
+
+    #[test]
+    fn legacy_transactions_are_left_without_a_list() {
+        let mut tx: TypedTransaction = TransactionRequest::new().gas_price(7).into();
+        assert!(!with_access_list(&mut tx, list()));
+        assert!(matches!(tx, TypedTransaction::Legacy(_)));
+        assert_eq!(tx.access_list(), None);
+    }
+
+    #[test]
+    fn typed_transactions_carry_the_list() {
+        let mut tx: TypedTransaction = Eip1559TransactionRequest::new().into();
+        assert!(with_access_list(&mut tx, list()));
+        assert!(matches!(tx, TypedTransaction::Eip1559(_)));
+        assert_eq!(tx.access_list(), Some(&list()));
+    }
+
+    #[tokio::test]
+    async fn no_list_is_generated_for_a_legacy_transaction() {
+        // Nothing listens here, so reaching the node would be reported as such
+        let provider = Provider::<Http>::try_from(dead_endpoint().await.unwrap()).unwrap();
+        let tx = TransactionRequest::new().into();
+        match generate_access_list(&provider, &tx).await {
+            AccessListCheck::Unavailable(reason) => assert_eq!(reason, LEGACY_NO_ACCESS_LIST),
+            check => panic!("expected Unavailable, got {:?}", check),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Token amounts that carry their decimals.
+//!
+//! A bare `U256` does not say whether it counts 6- or 18-decimal units,
+//! which is how `parse_ether(100)` ended up as the loan amount for a
+//! 6-decimal token. `TokenAmount` keeps the decimals next to the value,
+//! refuses arithmetic across different decimals and prints itself in
+//! whole tokens. Arithmetic is checked and returns errors rather than
+//! panicking.
+
+use crate::error::CapacitorError;
+use ethers::{
+    types::U256,
+    utils::{format_units, parse_units},
+};
+use eyre::Result;
+use serde::{Serialize, Serializer};
+use std::{cmp::Ordering, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenAmount {
+    raw: U256,
+    decimals: u8,
+}
+
+impl TokenAmount {
+    /// `raw` base units of a token with `decimals` decimals.
+    pub fn from_raw(raw: U256, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    /// Parse whole tokens such as `"100"` or `"2.5"`.
+    pub fn from_human(amount: &str, decimals: u8) -> Result<Self> {
+        let raw = parse_units(amount, u32::from(decimals))?.into();
+        Ok(Self { raw, decimals })
+    }
+
+    pub fn zero(decimals: u8) -> Self {
+        Self::from_raw(U256::zero(), decimals)
+    }
+
+    pub fn raw(self) -> U256 {
+        self.raw
+    }
+
+    pub fn decimals(self) -> u8 {
+        self.decimals
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.raw.is_zero()
+    }
+
+    /// Another amount of the same token.
+    pub fn with_raw(self, raw: U256) -> Self {
+        Self::from_raw(raw, self.decimals)
+    }
+
+    /// Fails with `AmountOverflow` when the sum does not fit a `U256`.
+    pub fn try_add(self, other: Self) -> Result<Self, CapacitorError> {
+        self.same_decimals(other)?;
+        let raw = self
+            .raw
+            .checked_add(other.raw)
+            .ok_or(CapacitorError::AmountOverflow {
+                left: self.raw,
+                right: other.raw,
+            })?;
+        Ok(self.with_raw(raw))
+    }
+
+    /// Fails with `AmountUnderflow` when `other` is the larger amount.
+    pub fn try_sub(self, other: Self) -> Result<Self, CapacitorError> {
+        self.same_decimals(other)?;
+        let raw = self
+            .raw
+            .checked_sub(other.raw)
+            .ok_or(CapacitorError::AmountUnderflow {
+                left: self.raw,
+                right: other.raw,
+            })?;
+        Ok(self.with_raw(raw))
+    }
+
+    pub fn saturating_sub(self, other: Self) -> Result<Self, CapacitorError> {
+        self.same_decimals(other)?;
+        Ok(self.with_raw(self.raw.saturating_sub(other.raw)))
+    }
+
+    fn same_decimals(self, other: Self) -> Result<(), CapacitorError> {
+        if self.decimals != other.decimals {
+            return Err(CapacitorError::DecimalsMismatch {
+                left: self.decimals,
+                right: other.decimals,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Amounts with different decimals are not comparable.
+impl PartialOrd for TokenAmount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self.decimals == other.decimals).then(|| self.raw.cmp(&other.raw))
+    }
+}
+
+/// Serialized as the raw base units, like a bare `U256`.
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match format_units(self.raw, u32::from(self.decimals)) {
+            Ok(human) => f.write_str(&human),
+            // Only reachable for more than 77 decimals
+            Err(_) => write!(f, "{} base units", self.raw),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subtracting_a_larger_amount_is_an_error() {
+        let small = TokenAmount::from_raw(U256::from(5), 6);
+        let large = TokenAmount::from_raw(U256::from(7), 6);
+        assert_eq!(large.try_sub(small).unwrap().raw(), U256::from(2));
+        assert!(matches!(
+            small.try_sub(large),
+            Err(CapacitorError::AmountUnderflow { left, right })
+                if left == U256::from(5) && right == U256::from(7)
+        ));
+        assert!(small.saturating_sub(large).unwrap().is_zero());
+    }
+
+    #[test]
+    fn adding_past_the_largest_amount_is_an_error() {
+        let max = TokenAmount::from_raw(U256::MAX, 6);
+        let one = TokenAmount::from_raw(U256::one(), 6);
+        assert!(matches!(
+            max.try_add(one),
+            Err(CapacitorError::AmountOverflow { left, right })
+                if left == U256::MAX && right == U256::one()
+        ));
+    }
+
+    #[test]
+    fn arithmetic_across_decimals_is_an_error() {
+        let six = TokenAmount::from_human("1", 6).unwrap();
+        let eighteen = TokenAmount::from_human("1", 18).unwrap();
+        for result in [six.try_add(eighteen), six.try_sub(eighteen)] {
+            assert!(matches!(
+                result,
+                Err(CapacitorError::DecimalsMismatch { left: 6, right: 18 })
+            ));
+        }
+        assert_eq!(six.partial_cmp(&eighteen), None);
+    }
+
+    #[test]
+    fn serializes_as_raw_units() {
+        let amount = TokenAmount::from_human("2.5", 6).unwrap();
+        assert_eq!(amount.to_string(), "2.500000");
+        assert_eq!(
+            serde_json::to_value(amount).unwrap(),
+            serde_json::to_value(U256::from(2_500_000)).unwrap()
+        );
+    }
+}
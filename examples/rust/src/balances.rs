@@ -0,0 +1,84 @@
+//! Token balance snapshots.
+
+use crate::abi::IERC20;
+use ethers::{
+    providers::Middleware,
+    types::{Address, U256},
+};
+use eyre::Result;
+use futures::{stream, StreamExt, TryStreamExt};
+use std::{collections::HashMap, sync::Arc};
+
+/// Most `balanceOf` calls in flight at once, to stay under RPC rate limits.
+pub const BALANCE_CONCURRENCY: usize = 8;
+
+/// Read `holder`'s balance of every token in `tokens` concurrently.
+pub async fn snapshot_balances<M: Middleware + 'static>(
+    client: Arc<M>,
+    holder: Address,
+    tokens: &[Address],
+) -> Result<HashMap<Address, U256>> {
+    stream::iter(tokens.iter().copied())
+        .map(|token| {
+            let erc20 = IERC20::new(token, client.clone());
+            async move {
+                let balance = erc20.balance_of(holder).call().await?;
+                Ok::<_, eyre::Report>((token, balance))
+            }
+        })
+        .buffer_unordered(BALANCE_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use ethers::{
+        abi::{self, Token},
+        providers::{JsonRpcClient, Provider, ProviderError},
+        types::Bytes,
+    };
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::Value;
+    use std::fmt::Debug;
+
+    /// Mock tokens where every holder's balance is the token's last
+    /// address byte times a thousand.
+    #[derive(Debug)]
+    struct Tokens;
+
+    #[async_trait]
+    impl JsonRpcClient for Tokens {
+        type Error = ProviderError;
+
+        async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+        where
+            P: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned + Send,
+        {
+            assert_eq!(method, "eth_call");
+            let params = serde_json::to_value(params)?;
+            let token: Address = serde_json::from_value(params[0]["to"].clone())?;
+            let balance = U256::from(token.as_bytes()[19]) * 1_000;
+            let encoded = Bytes::from(abi::encode(&[Token::Uint(balance)]));
+            Ok(serde_json::from_value(Value::from(encoded.to_string()))?)
+        }
+    }
+
+    #[tokio::test]
+    async fn every_token_is_read() {
+        let client = Arc::new(Provider::new(Tokens));
+        let tokens: Vec<Address> = (1..=20).map(Address::from_low_u64_be).collect();
+
+        let balances = snapshot_balances(client, Address::repeat_byte(0xaa), &tokens)
+            .await
+            .unwrap();
+
+        assert_eq!(balances.len(), tokens.len());
+        for token in &tokens {
+            assert_eq!(balances[token], U256::from(token.as_bytes()[19]) * 1_000);
+        }
+    }
+}
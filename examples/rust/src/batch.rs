@@ -0,0 +1,102 @@
+//! Reading everything a batch of loans needs before any of it is sent.
+//!
+//! Sends from one account go out one at a time, in nonce order, but the
+//! reads before them don't have to. [`prefetch_batch`] reads every job's
+//! token metadata, pool liquidity and fee concurrently, with at most
+//! [`PREFETCH_CONCURRENCY`] jobs in flight. The provider's fee function is
+//! probed once before that, so concurrent jobs don't each probe it. The
+//! requests still pass through the client's rate limiter, so a configured
+//! `rate_limit` is honoured.
+
+use crate::{
+    amount::TokenAmount,
+    client::FlashLoanClient,
+    error::CapacitorError,
+    fee_resolver::{FeeInfo, FeeResolver},
+    pool::pool_liquidity,
+    tokens::{token_decimals, token_symbol},
+};
+use ethers::types::{Address, U256};
+use eyre::{Result, WrapErr};
+use futures::{stream, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+
+/// Most jobs whose reads are in flight at once.
+pub const PREFETCH_CONCURRENCY: usize = 8;
+
+/// One loan of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub token: Address,
+    /// Whole tokens, such as `"2.5"`; converted with the token's decimals.
+    pub amount: String,
+}
+
+/// What the read phase learned about a [`Job`].
+#[derive(Debug, Clone)]
+pub struct JobContext {
+    pub token: Address,
+    pub symbol: String,
+    pub amount: TokenAmount,
+    pub fee: FeeInfo,
+    /// The provider's balance of the token, the largest loan it can serve.
+    pub max_loan: U256,
+}
+
+impl JobContext {
+    /// Whether the pool holds enough for this job's loan.
+    pub fn fits(&self) -> bool {
+        self.amount.raw() <= self.max_loan
+    }
+}
+
+/// Read what each of `jobs` needs from `provider`, returned in job order.
+///
+/// Fees fall back to [`FALLBACK_FEE_BPS`](crate::fee_resolver::FALLBACK_FEE_BPS)
+/// when the provider has no fee function. `decimals_override` is used for tokens whose `decimals()`
+/// call fails, as in [`token_decimals`].
+pub async fn prefetch_batch(
+    client: &FlashLoanClient,
+    provider: Address,
+    jobs: &[Job],
+    decimals_override: &HashMap<Address, u8>,
+) -> Result<Vec<JobContext>> {
+    let Some(first) = jobs.first() else {
+        return Ok(Vec::new());
+    };
+    let resolver = FeeResolver::new(client.owner.clone(), first.token);
+    let resolver = &resolver;
+    // Probe before fanning out; the resolver doesn't coalesce concurrent probes
+    let has_fee_fn = match resolver.resolve(provider).await {
+        Ok(_) => true,
+        Err(e) if matches!(e.downcast_ref(), Some(CapacitorError::Unsupported(_))) => false,
+        Err(e) => return Err(e),
+    };
+
+    stream::iter(jobs)
+        .map(|job| async move {
+            let token = job.token;
+            let (symbol, decimals, max_loan) = futures::join!(
+                token_symbol(client.owner.clone(), token),
+                token_decimals(client.owner.clone(), token, decimals_override),
+                pool_liquidity(client.owner.clone(), provider, token),
+            );
+            let amount = TokenAmount::from_human(&job.amount, decimals?)
+                .wrap_err_with(|| format!("invalid amount {} for {}", job.amount, symbol))?;
+            let fee = if has_fee_fn {
+                resolver.fee_info(provider, token, amount.raw()).await?
+            } else {
+                FeeInfo::fallback(amount.raw())
+            };
+            Ok::<_, eyre::Report>(JobContext {
+                token,
+                symbol,
+                amount,
+                fee,
+                max_loan: max_loan?,
+            })
+        })
+        .buffered(PREFETCH_CONCURRENCY)
+        .try_collect()
+        .await
+}
@@ -0,0 +1,122 @@
+//! Cancelling stuck transactions.
+//!
+//! A pending transaction is replaced by a 0-value self-transfer at the same
+//! nonce, paying more gas so the node accepts it as a replacement. Once the
+//! replacement mines, the nonce is used up and later sends can go through.
+
+use crate::{
+    client::{FlashLoanClient, SignerClient},
+    poll::wait_for_receipt,
+};
+use ethers::{prelude::*, types::transaction::eip2718::TypedTransaction};
+use eyre::{eyre, Result};
+use std::sync::Arc;
+
+/// A replacement pays 125% of the original fee: a 25% bump, above the
+/// 10% minimum nodes require.
+const FEE_BUMP_PERCENT: u64 = 125;
+
+const TRANSFER_GAS: u64 = 21_000;
+
+#[derive(Debug, Clone)]
+pub enum CancelOutcome {
+    /// The replacement mined; the original will never execute.
+    Cancelled(TransactionReceipt),
+    /// The original mined before it could be replaced.
+    AlreadyMined(TransactionReceipt),
+}
+
+/// Replace the pending transaction `pending` with a 0-value self-transfer.
+///
+/// The replacement is signed by whichever of the client's signers sent the
+/// original. If the original has already mined (or mines while the
+/// replacement is in flight) its receipt is reported instead.
+pub async fn cancel(client: &FlashLoanClient, pending: H256) -> Result<CancelOutcome> {
+    let tx = client
+        .provider
+        .get_transaction(pending)
+        .await?
+        .ok_or_else(|| eyre!("transaction {:?} not found", pending))?;
+
+    if tx.block_number.is_some() {
+        return Ok(CancelOutcome::AlreadyMined(
+            mined_receipt(client, pending).await?,
+        ));
+    }
+
+    let signer = signer_for(client, tx.from)?;
+    let replacement = replacement(&signer, &tx).await?;
+
+    let sent = match client.send_transaction(&signer, replacement).await {
+        Ok(sent) => sent,
+        // Most likely "nonce too low": the original mined in the meantime
+        Err(e) => match client.provider.get_transaction_receipt(pending).await? {
+            Some(receipt) => return Ok(CancelOutcome::AlreadyMined(receipt)),
+            None => return Err(e),
+        },
+    };
+
+    match wait_for_receipt(sent, client.poll).await? {
+        Some(receipt) => Ok(CancelOutcome::Cancelled(receipt)),
+        None => match client.provider.get_transaction_receipt(pending).await? {
+            Some(receipt) => Ok(CancelOutcome::AlreadyMined(receipt)),
+            None => Err(eyre!("cancel transaction was dropped from the mempool")),
+        },
+    }
+}
+
+fn signer_for(client: &FlashLoanClient, from: Address) -> Result<Arc<SignerClient>> {
+    if from == client.owner_address() {
+        Ok(client.owner.clone())
+    } else if from == client.funder_address() {
+        Ok(client.funder.clone())
+    } else {
+        Err(eyre!(
+            "transaction was sent by {:?}, which is neither the owner nor the funder",
+            from
+        ))
+    }
+}
+
+/// Same nonce and transaction type as `original`, with bumped fees.
+async fn replacement(signer: &SignerClient, original: &Transaction) -> Result<TypedTransaction> {
+    let from = original.from;
+
+    let mut tx: TypedTransaction =
+        match (original.max_fee_per_gas, original.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(priority_fee)) => {
+                let (current_max_fee, current_priority_fee) =
+                    signer.estimate_eip1559_fees(None).await?;
+                Eip1559TransactionRequest::new()
+                    .max_fee_per_gas(bump(max_fee).max(current_max_fee))
+                    .max_priority_fee_per_gas(bump(priority_fee).max(current_priority_fee))
+                    .into()
+            }
+            _ => {
+                let original_price = original.gas_price.unwrap_or_default();
+                let current_price = signer.get_gas_price().await?;
+                TransactionRequest::new()
+                    .gas_price(bump(original_price).max(current_price))
+                    .into()
+            }
+        };
+
+    tx.set_from(from)
+        .set_to(from)
+        .set_value(U256::zero())
+        .set_nonce(original.nonce)
+        .set_gas(TRANSFER_GAS);
+    Ok(tx)
+}
+
+fn bump(fee: U256) -> U256 {
+    fee * FEE_BUMP_PERCENT / 100 + 1
+}
+
+async fn mined_receipt(client: &FlashLoanClient, hash: H256) -> Result<TransactionReceipt> {
+    client
+        .provider
+        .get_transaction_receipt(hash)
+        .await?
+        .ok_or_else(|| eyre!("receipt for {:?} is not available yet", hash))
+}
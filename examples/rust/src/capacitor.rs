@@ -0,0 +1,1173 @@
+// Capacitor command-line tools
+//
+// Utilities around the flash loan provider and your deployed tester
+// contract. Deployment and execution live in the `deploy` and `execute`
+// binaries; everything else is a subcommand here.
+
+use clap::{Parser, Subcommand};
+use ethers::{
+    prelude::*,
+    utils::{format_units, parse_units},
+};
+use eyre::Result;
+use flashloan_example::{
+    abi::{IFlashLoanTester, IERC20},
+    amount::TokenAmount,
+    batch::{prefetch_batch, Job},
+    cancel::{cancel, CancelOutcome},
+    client::{failover, read_only},
+    confirm::{confirm_irreversible, confirm_send, Summary},
+    cost::cost_report,
+    deployment::{load_tester_artifact, verify_bytecode},
+    error::exit_code,
+    events::FlashLoanExecuted,
+    fee_resolver::{FeeResolver, FALLBACK_FEE_BPS},
+    flash_loan::execute_flash_loan_multi,
+    history::flash_loan_history,
+    inspect::inspect_tx,
+    ownership::transfer_ownership,
+    poll::TxStatus,
+    pool::{pool_liquidity, supported_tokens},
+    preflight::check_loan_cap,
+    profit::estimate_profit,
+    raw::{call_raw, parse_args, parse_function, send_raw},
+    reconcile::{reconcile, sweep},
+    self_test::{fund_fee, self_test},
+    tokens::{token_decimals, token_symbol, TokenBook},
+    watch::watch_flash_loans,
+    CapacitorError, Config, FlashLoanClient, Network,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    process,
+    sync::Arc,
+};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(name = "capacitor", about = "Flash loan tools for Plasma")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Skip the mainnet confirmation prompt
+    #[arg(long, global = true)]
+    yes: bool,
+
+    /// Flash loan provider to use instead of the configured one
+    #[arg(long, global = true, value_name = "ADDRESS")]
+    provider: Option<Address>,
+
+    /// Gas limit to send with if estimation reverts, instead of aborting
+    #[arg(long, global = true, value_name = "LIMIT")]
+    force_gas: Option<u64>,
+
+    /// Serve Prometheus metrics on this address, e.g. 127.0.0.1:9100
+    #[cfg(feature = "metrics")]
+    #[arg(long, global = true, value_name = "ADDR")]
+    metrics_addr: Option<std::net::SocketAddr>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print flash loan events live as they are mined
+    Watch {
+        /// Contract emitting the events (defaults to the flash loan provider)
+        #[arg(long)]
+        address: Option<Address>,
+    },
+    /// Show the network, the provider's liquidity and your contract
+    Status,
+    /// Check the configuration and RPC endpoints
+    Doctor,
+    /// List the tokens the flash loan provider lends
+    Tokens,
+    /// List past flash loans taken by an initiator
+    History {
+        /// First block to search
+        #[arg(long = "from")]
+        from_block: u64,
+        /// Last block to search (defaults to the latest block)
+        #[arg(long = "to")]
+        to_block: Option<u64>,
+        /// Borrower to list loans for (defaults to deployed_contract)
+        #[arg(long)]
+        initiator: Option<Address>,
+    },
+    /// Show the status, gas and flash loan fees of a transaction
+    Inspect {
+        /// Hash of the transaction
+        tx_hash: H256,
+    },
+    /// Borrow several tokens in one transaction (needs executeFlashLoanBatch)
+    FlashMulti {
+        /// TOKEN=AMOUNT in whole tokens, e.g. TUSDT=100; repeat per token
+        #[arg(long = "loan", value_name = "TOKEN=AMOUNT", required = true)]
+        loans: Vec<String>,
+        /// FlashLoanTester mode (0 = repay normally)
+        #[arg(long, default_value_t = 0)]
+        mode: u8,
+        /// Extra bytes passed through to the callback
+        #[arg(long, default_value = "0x")]
+        params: Bytes,
+    },
+    /// Call any function on your contract by its signature
+    Call {
+        /// e.g. 'function owner() view returns (address)'
+        signature: String,
+        /// Arguments, one per input
+        args: Vec<String>,
+        /// Contract to call (defaults to deployed_contract)
+        #[arg(long)]
+        to: Option<Address>,
+        /// Send a transaction from the owner instead of an eth_call
+        #[arg(long)]
+        send: bool,
+    },
+    /// Run a small loan in every tester mode and check each result
+    SelfTest {
+        /// Token to borrow: a known symbol or a 0x address (defaults to token)
+        #[arg(long)]
+        token: Option<String>,
+        /// Amount to borrow in whole tokens
+        #[arg(long, default_value = "1")]
+        amount: String,
+    },
+    /// Compare the contract's balance with the next loan's fee
+    Reconcile {
+        /// Size of the next loan, in whole tokens
+        #[arg(long)]
+        amount: String,
+        /// Token to check: a known symbol or a 0x address (defaults to token)
+        #[arg(long)]
+        token: Option<String>,
+        /// Withdraw the surplus to the owner
+        #[arg(long)]
+        sweep: bool,
+    },
+    /// Estimate a buy-then-sell round trip's profit offline
+    EstimateProfit {
+        /// Loan size in whole tokens
+        #[arg(long)]
+        amount: f64,
+        /// Buy price, in loan tokens per unit of the asset
+        #[arg(long)]
+        buy: f64,
+        /// Sell price, in loan tokens per unit of the asset
+        #[arg(long)]
+        sell: f64,
+        /// Gas cost of the transaction, in loan tokens
+        #[arg(long, default_value_t = 0.0)]
+        gas: f64,
+        /// Flash loan fee in bps
+        #[arg(long, default_value_t = FALLBACK_FEE_BPS)]
+        fee_bps: u32,
+    },
+    /// Replace a stuck transaction with a 0-value self-transfer
+    Cancel {
+        /// Hash of the pending transaction
+        tx_hash: H256,
+    },
+    /// Hand your deployed contract to a new owner
+    TransferOwnership {
+        /// Address that will own the contract
+        new_owner: Address,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        process::exit(exit_code(&e));
+    }
+}
+
+async fn run() -> Result<()> {
+    let cli = Cli::parse();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    let mut config = Config::load().map_err(|e| CapacitorError::Config(format!("{:#}", e)))?;
+    if let Some(provider) = cli.provider {
+        let code = read_only(&config)?.get_code(provider, None).await?;
+        if code.is_empty() {
+            return Err(CapacitorError::NoContractCode(provider).into());
+        }
+        config.flash_loan_provider = provider;
+    }
+    if let Some(limit) = cli.force_gas {
+        config.fallback_gas_limit = Some(limit);
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = cli.metrics_addr {
+        flashloan_example::metrics::serve(addr).await?;
+        println!("📈 Metrics: http://{}/metrics", addr);
+    }
+
+    match cli.command {
+        Command::Watch { address } => watch(&config, address).await,
+        Command::Status => status(&config).await,
+        Command::Doctor => doctor(&config).await,
+        Command::Tokens => tokens(&config).await,
+        Command::History {
+            from_block,
+            to_block,
+            initiator,
+        } => history(&config, from_block, to_block, initiator).await,
+        Command::Inspect { tx_hash } => inspect(&config, tx_hash).await,
+        Command::FlashMulti {
+            loans,
+            mode,
+            params,
+        } => flash_multi(&config, &loans, mode, params, cli.yes).await,
+        Command::Call {
+            signature,
+            args,
+            to,
+            send,
+        } => call(&config, &signature, &args, to, send, cli.yes).await,
+        Command::SelfTest { token, amount } => {
+            self_test_cmd(&config, token.as_deref(), &amount, cli.yes).await
+        }
+        Command::Reconcile {
+            amount,
+            token,
+            sweep,
+        } => reconcile_cmd(&config, &amount, token.as_deref(), sweep, cli.yes).await,
+        Command::EstimateProfit {
+            amount,
+            buy,
+            sell,
+            gas,
+            fee_bps,
+        } => estimate_profit_cmd(amount, buy, sell, gas, fee_bps),
+        Command::Cancel { tx_hash } => cancel_tx(&config, tx_hash, cli.yes).await,
+        Command::TransferOwnership { new_owner } => {
+            transfer_ownership_cmd(&config, new_owner, cli.yes).await
+        }
+    }
+}
+
+async fn watch(config: &Config, address: Option<Address>) -> Result<()> {
+    println!("\n👀 Watch Flash Loans\n");
+    println!("{}", "=".repeat(60));
+
+    let Some(ws_url) = config.rpc.ws_url.as_deref() else {
+        println!("\n❌ Error: No WebSocket endpoint configured!");
+        println!("\nPlease set ws_url under [rpc] in capacitor.toml:");
+        println!("   ws_url = \"wss://...\"");
+        return Err(CapacitorError::Config("rpc.ws_url is not set".into()).into());
+    };
+
+    let address = address.unwrap_or(config.flash_loan_provider);
+    let provider = Provider::<Ws>::connect(ws_url).await?;
+    let client = Arc::new(provider.clone());
+
+    println!("\n📍 Network: {}", config.network);
+    println!("📡 Endpoint: {}", ws_url);
+    println!("📄 Watching: {:?}", address);
+    println!("\n⏳ Waiting for flash loans... (Ctrl+C to stop)");
+
+    let mut tokens: HashMap<Address, (String, u32)> = HashMap::new();
+    let mut events = Box::pin(watch_flash_loans(&provider, address).await?);
+
+    while let Some(event) = events.next().await {
+        if let Entry::Vacant(entry) = tokens.entry(event.token) {
+            entry.insert(token_info(client.clone(), event.token, config).await?);
+        }
+        let (symbol, decimals) = &tokens[&event.token];
+        #[cfg(feature = "metrics")]
+        flashloan_example::metrics::record_observed();
+        print_event(config.network, &event, symbol, *decimals)?;
+    }
+
+    println!("\n❌ Subscription closed by the node");
+    Ok(())
+}
+
+async fn status(config: &Config) -> Result<()> {
+    println!("\n📊 Capacitor Status\n");
+    println!("{}", "=".repeat(60));
+
+    let provider = Arc::new(read_only(config)?);
+    let chain_id = provider.get_chainid().await?;
+    let block = provider.get_block_number().await?;
+
+    println!("\n📍 Network: {}", config.network);
+    println!("📡 Chain ID: {}", chain_id);
+    println!("🧱 Latest Block: {}", block);
+
+    let (symbol, decimals) = token_info(provider.clone(), config.token, config).await?;
+    let liquidity =
+        pool_liquidity(provider.clone(), config.flash_loan_provider, config.token).await?;
+
+    println!("\n🏦 Flash Loan Provider: {:?}", config.flash_loan_provider);
+    println!(
+        "   Liquidity: {} {}",
+        format_units(liquidity, decimals)?,
+        symbol
+    );
+
+    match config.deployed_contract {
+        Some(contract) => {
+            let tester = IFlashLoanTester::new(contract, provider.clone());
+            let balance = IERC20::new(config.token, provider.clone())
+                .balance_of(contract)
+                .await?;
+            println!("\n📄 Contract: {:?}", contract);
+            println!("   Owner: {:?}", tester.owner().await?);
+            println!(
+                "   Balance: {} {}",
+                format_units(balance, decimals)?,
+                symbol
+            );
+        }
+        None => println!("\n📄 Contract: not deployed (run `cargo run --bin deploy`)"),
+    }
+
+    println!();
+    Ok(())
+}
+
+async fn tokens(config: &Config) -> Result<()> {
+    println!("\n🪙 Supported Tokens\n");
+    println!("{}", "=".repeat(60));
+
+    let provider = Arc::new(read_only(config)?);
+    println!("\n📍 Network: {}", config.network);
+    println!("🏦 Flash Loan Provider: {:?}", config.flash_loan_provider);
+
+    let tokens = supported_tokens(provider.clone(), config.flash_loan_provider).await?;
+    if tokens.is_empty() {
+        println!("\nThe provider lists no tokens.\n");
+        return Ok(());
+    }
+
+    let book = TokenBook::from_config(config);
+    println!(
+        "\n{:<10}  {:<42}  {:<7}  Max Loan",
+        "Symbol", "Address", "Enabled"
+    );
+    for token in tokens {
+        let (onchain_symbol, decimals) = token_info(provider.clone(), token, config).await?;
+        let symbol = book
+            .symbol_of(config.network, token)
+            .map(str::to_string)
+            .unwrap_or(onchain_symbol);
+        // The provider can only lend what it holds
+        let max_loan = pool_liquidity(provider.clone(), config.flash_loan_provider, token).await?;
+        println!(
+            "{:<10}  {:<42}  {:<7}  {}",
+            symbol,
+            format!("{:?}", token),
+            if max_loan.is_zero() { "no" } else { "yes" },
+            format_units(max_loan, decimals)?
+        );
+    }
+
+    println!();
+    Ok(())
+}
+
+async fn doctor(config: &Config) -> Result<()> {
+    println!("\n🩺 Capacitor Doctor\n");
+    println!("{}", "=".repeat(60));
+
+    println!("\n📍 Network: {}", config.network);
+    check(
+        "Owner key",
+        config.owner_key().is_some(),
+        "set owner_key or OWNER_KEY",
+    );
+    check(
+        "Deployed contract",
+        config.deployed_contract.is_some(),
+        "run `cargo run --bin deploy` and set deployed_contract",
+    );
+    check(
+        "WebSocket endpoint",
+        config.rpc.ws_url.is_some(),
+        "set ws_url under [rpc] to use `watch`",
+    );
+
+    let provider = read_only(config)?;
+    match provider.get_chainid().await {
+        Ok(chain_id) => check(
+            &format!("Chain ID {}", chain_id),
+            chain_id == config.network.chain_id().into(),
+            &format!(
+                "{} expects chain ID {}",
+                config.network,
+                config.network.chain_id()
+            ),
+        ),
+        Err(e) => check("RPC reachable", false, &e.to_string()),
+    }
+
+    // Only when there is something to compare against
+    if let (Some(contract), Ok(artifact)) = (
+        config.deployed_contract,
+        load_tester_artifact(config.artifact.as_deref()),
+    ) {
+        if artifact.deployed.is_some() {
+            match verify_bytecode(&provider, contract, &artifact).await {
+                Ok(matches) => check(
+                    "Deployed bytecode matches the artifact",
+                    matches,
+                    "compiled with different settings or from a different version; \
+                     redeploy or point deployed_contract at the right contract",
+                ),
+                Err(e) => check("Deployed bytecode", false, &e.to_string()),
+            }
+        }
+    }
+
+    let Some(endpoints) = failover(&provider) else {
+        println!("\nReplaying a recorded session; endpoints not checked.\n");
+        return Ok(());
+    };
+
+    // A few rounds so the latency averages settle
+    for _ in 0..3 {
+        endpoints.probe().await;
+    }
+
+    println!("\n📡 Endpoints (fastest healthy endpoint is used first):");
+    for stat in endpoints.endpoint_stats() {
+        let latency = stat
+            .ema_latency
+            .map(|latency| format!("{} ms", latency.as_millis()))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "   {} {}  latency {}  failures {}/{}",
+            if stat.healthy { "✅" } else { "❌" },
+            stat.url,
+            latency,
+            stat.failures,
+            stat.requests
+        );
+    }
+    println!("   Preferred: {}\n", endpoints.current());
+    Ok(())
+}
+
+fn check(what: &str, ok: bool, hint: &str) {
+    if ok {
+        println!("✅ {}", what);
+    } else {
+        println!("❌ {} ({})", what, hint);
+    }
+}
+
+async fn history(
+    config: &Config,
+    from_block: u64,
+    to_block: Option<u64>,
+    initiator: Option<Address>,
+) -> Result<()> {
+    println!("\n📜 Flash Loan History\n");
+    println!("{}", "=".repeat(60));
+
+    let Some(initiator) = initiator.or(config.deployed_contract) else {
+        println!("\n❌ Error: No initiator given!");
+        println!("\nPass --initiator 0x... or set deployed_contract in capacitor.toml");
+        return Err(CapacitorError::Config(
+            "no --initiator given and deployed_contract is not set".into(),
+        )
+        .into());
+    };
+
+    let provider = Arc::new(read_only(config)?);
+    let to_block = match to_block {
+        Some(block) => block,
+        None => provider.get_block_number().await?.as_u64(),
+    };
+
+    println!("\n📍 Network: {}", config.network);
+    println!("👤 Initiator: {:?}", initiator);
+    println!("🔎 Blocks: {} to {}", from_block, to_block);
+
+    let events = flash_loan_history(
+        &*provider,
+        config.flash_loan_provider,
+        initiator,
+        from_block,
+        to_block,
+    )
+    .await?;
+
+    if events.is_empty() {
+        println!("\nNo flash loans found.\n");
+        return Ok(());
+    }
+
+    let mut tokens: HashMap<Address, (String, u32)> = HashMap::new();
+    println!(
+        "\n{:>10}  {:<66}  {:>24}  {:>20}  Token",
+        "Block", "Transaction", "Amount", "Fee"
+    );
+    for event in &events {
+        if let Entry::Vacant(entry) = tokens.entry(event.token) {
+            entry.insert(token_info(provider.clone(), event.token, config).await?);
+        }
+        let (symbol, decimals) = &tokens[&event.token];
+        println!(
+            "{:>10}  {:<66}  {:>24}  {:>20}  {}",
+            event.block_number.unwrap_or_default(),
+            event
+                .tx_hash
+                .map(|hash| format!("{:?}", hash))
+                .unwrap_or_default(),
+            format_units(event.amount, *decimals)?,
+            format_units(event.fee, *decimals)?,
+            symbol
+        );
+    }
+    println!("\n{} flash loan(s)\n", events.len());
+    Ok(())
+}
+
+async fn inspect(config: &Config, tx_hash: H256) -> Result<()> {
+    println!("\n🔎 Inspect Transaction\n");
+    println!("{}", "=".repeat(60));
+
+    let provider = Arc::new(read_only(config)?);
+    println!("\n📍 Network: {}", config.network);
+    println!("🔗 Transaction: {:?}", tx_hash);
+
+    let inspection = inspect_tx(&provider, tx_hash).await?;
+    let Some(receipt) = inspection.receipt() else {
+        match inspection.status {
+            TxStatus::Pending => {
+                println!("\n⏳ Pending in mempool, not mined yet");
+                println!(
+                    "   Replace it with: cargo run --bin capacitor -- cancel {:?}",
+                    tx_hash
+                );
+            }
+            _ => {
+                println!("\n❓ Not found by the node");
+                println!("   It was dropped, never sent, or is on another network");
+            }
+        }
+        println!();
+        return Ok(());
+    };
+
+    let block = receipt
+        .block_number
+        .map(|block| block.to_string())
+        .unwrap_or_default();
+    if inspection.succeeded() == Some(true) {
+        println!("\n✅ Succeeded in block {}", block);
+    } else {
+        println!("\n❌ Reverted in block {}", block);
+    }
+    if let Some(from) = inspection.from {
+        println!("👤 From: {:?}", from);
+    }
+    if let Some(to) = inspection.to {
+        println!("📄 To: {:?}", to);
+    }
+    let cost = cost_report(receipt, config.xpl_price_usd);
+    println!("⛽ Gas Used: {}", cost.gas_used);
+    println!("💸 Cost: {}", cost);
+
+    // Every fee token also has an event, so this covers both
+    let mut tokens = HashMap::new();
+    for event in &inspection.events {
+        if let Entry::Vacant(entry) = tokens.entry(event.token) {
+            entry.insert(token_info(provider.clone(), event.token, config).await?);
+        }
+        let (symbol, decimals) = &tokens[&event.token];
+        print_event(config.network, event, symbol, *decimals)?;
+    }
+
+    if inspection.fees.is_empty() {
+        println!("\nNo flash loan events in this transaction.");
+    } else {
+        println!("\n💰 Fees Paid:");
+        for (token, fee) in &inspection.fees {
+            let (symbol, decimals) = &tokens[token];
+            println!("   {} {}", format_units(*fee, *decimals)?, symbol);
+        }
+    }
+    println!();
+    Ok(())
+}
+
+async fn flash_multi(
+    config: &Config,
+    loans: &[String],
+    mode: u8,
+    params: Bytes,
+    yes: bool,
+) -> Result<()> {
+    println!("\n⚡ Multi-Token Flash Loan\n");
+    println!("{}", "=".repeat(60));
+
+    let Some(tester) = config.deployed_contract else {
+        println!("\n❌ Error: Invalid contract address!");
+        println!("\nPlease set deployed_contract in capacitor.toml");
+        return Err(CapacitorError::Config("deployed_contract is not set".into()).into());
+    };
+    if config.owner_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key in capacitor.toml");
+        println!("   OR export OWNER_KEY=0x...your key...");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
+    }
+
+    let client = FlashLoanClient::connect(config).await?;
+    let book = TokenBook::from_config(config);
+
+    let mut jobs = Vec::new();
+    for loan in loans {
+        let Some((token, amount)) = loan.split_once('=') else {
+            eyre::bail!("--loan expects TOKEN=AMOUNT, got {}", loan);
+        };
+        jobs.push(Job {
+            token: book.resolve(config.network, token)?,
+            amount: amount.to_string(),
+        });
+    }
+
+    // Everything is read up front, concurrently; only the send is sequential
+    let contexts = prefetch_batch(
+        &client,
+        config.flash_loan_provider,
+        &jobs,
+        &config.decimals_override,
+    )
+    .await?;
+    let tokens: Vec<Address> = contexts.iter().map(|job| job.token).collect();
+    let amounts: Vec<TokenAmount> = contexts.iter().map(|job| job.amount).collect();
+    let fees: Vec<TokenAmount> = contexts
+        .iter()
+        .map(|job| job.amount.with_raw(job.fee.absolute))
+        .collect();
+    let symbols: HashMap<Address, String> = contexts
+        .iter()
+        .map(|job| (job.token, job.symbol.clone()))
+        .collect();
+
+    println!("\n📍 Network: {}", config.network);
+    println!("📄 Contract: {:?}", tester);
+    println!("\n📋 Loans:");
+    for job in &contexts {
+        println!(
+            "   {} {} (fee {} {}, {} bps)",
+            job.amount,
+            job.symbol,
+            job.amount.with_raw(job.fee.absolute),
+            job.symbol,
+            job.fee.bps
+        );
+    }
+
+    if let Some(job) = contexts.iter().find(|job| !job.fits()) {
+        println!(
+            "\n❌ Error: Pool has {} {} available, requested {}",
+            job.amount.with_raw(job.max_loan),
+            job.symbol,
+            job.amount
+        );
+        return Err(CapacitorError::PoolInsufficientLiquidity {
+            requested: job.amount.raw(),
+            available: job.max_loan,
+        }
+        .into());
+    }
+
+    for job in &contexts {
+        let cap = config.max_loan_amount(job.amount.decimals())?;
+        if let Err(e) = check_loan_cap(job.amount.raw(), cap) {
+            if let Some(cap) = cap {
+                println!(
+                    "\n❌ Error: Loan of {} {} exceeds max_loan_amount {} {}",
+                    job.amount,
+                    job.symbol,
+                    job.amount.with_raw(cap),
+                    job.symbol
+                );
+            }
+            return Err(e.into());
+        }
+    }
+
+    let mut summary = Summary::new(config.network, "Batch flash loan");
+    for job in &contexts {
+        summary = summary.with(
+            job.symbol.clone(),
+            format!(
+                "{} (fee {}, {} bps)",
+                job.amount,
+                job.amount.with_raw(job.fee.absolute),
+                job.fee.bps
+            ),
+        );
+    }
+    if !confirm_send(&summary, yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    println!("\n⏳ Executing batch flash loan...");
+    let outcome =
+        execute_flash_loan_multi(&client, tester, &tokens, &amounts, &fees, mode, params).await?;
+
+    println!("📝 Transaction: {:?}", outcome.tx_hash);
+    println!("   View: {}", config.network.tx_url(outcome.tx_hash));
+    println!(
+        "   Status: {}",
+        if outcome.success {
+            "✅ Success"
+        } else {
+            "❌ Failed"
+        }
+    );
+    println!("\n✅ Fees per token:");
+    for leg in &outcome.legs {
+        println!(
+            "   {}: expected {}, paid {} {}",
+            symbols[&leg.token],
+            leg.expected_fee,
+            leg.actual_fee,
+            if leg.actual_fee == leg.expected_fee {
+                "✅"
+            } else {
+                "❌"
+            }
+        );
+    }
+    println!();
+    if !outcome.success {
+        return Err(CapacitorError::Reverted(outcome.tx_hash).into());
+    }
+    Ok(())
+}
+
+async fn self_test_cmd(
+    config: &Config,
+    token: Option<&str>,
+    amount: &str,
+    yes: bool,
+) -> Result<()> {
+    println!("\n🧪 Flash Loan Self-Test\n");
+    println!("{}", "=".repeat(60));
+
+    let Some(tester) = config.deployed_contract else {
+        println!("\n❌ Error: Invalid contract address!");
+        println!("\nPlease set deployed_contract in capacitor.toml");
+        return Err(CapacitorError::Config("deployed_contract is not set".into()).into());
+    };
+    if config.owner_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key in capacitor.toml");
+        println!("   OR export OWNER_KEY=0x...your key...");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
+    }
+
+    let client = FlashLoanClient::connect(config).await?;
+    let token = match token {
+        Some(token) => TokenBook::from_config(config).resolve(config.network, token)?,
+        None => config.token,
+    };
+    let (symbol, decimals) = token_info(client.owner.clone(), token, config).await?;
+    let amount = TokenAmount::from_human(amount, decimals as u8)?;
+    let cap = config.max_loan_amount(decimals as u8)?;
+    if let Err(e) = check_loan_cap(amount.raw(), cap) {
+        if let Some(cap) = cap {
+            println!(
+                "\n❌ Error: Loan of {} {} exceeds max_loan_amount {} {}",
+                amount,
+                symbol,
+                amount.with_raw(cap),
+                symbol
+            );
+        }
+        return Err(e.into());
+    }
+
+    let fee_info = FeeResolver::new(client.owner.clone(), token)
+        .fee_info_or_fallback(config.flash_loan_provider, token, amount.raw())
+        .await?;
+    let fee = amount.with_raw(fee_info.absolute);
+
+    println!("\n📍 Network: {}", config.network);
+    println!("📄 Contract: {:?}", tester);
+    println!("💰 Loan: {} {}", amount, symbol);
+
+    let summary = Summary::new(
+        config.network,
+        "Fund contract and run the flash loan self-test",
+    )
+    .with("Token", format!("{} ({:?})", symbol, token))
+    .with("Amount", format!("{} {}", amount, symbol))
+    .with("Fee", format!("{} {} ({} bps)", fee, symbol, fee_info.bps));
+    if !confirm_send(&summary, yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    if let Some(receipt) = fund_fee(&client, tester, token, fee).await? {
+        println!("💸 Funded fee: {:?}", receipt.transaction_hash);
+    }
+
+    println!("\n⏳ Running every mode...");
+    let checks = self_test(&client, tester, token, amount, fee).await?;
+
+    println!(
+        "\n{:<14}  {:<8}  {:<8}  {:<6}  Detail",
+        "Mode", "Expected", "Got", "Result"
+    );
+    for check in &checks {
+        let outcome = |succeeded: bool| if succeeded { "success" } else { "revert" };
+        let detail = match (&check.tx_hash, &check.reason) {
+            (Some(hash), None) => format!("{:?}", hash),
+            (_, Some(reason)) if check.wrong_reason() => format!(
+                "{} (expected a reason mentioning {})",
+                reason,
+                check.mode.expected_revert().unwrap_or_default().join(" / ")
+            ),
+            (_, Some(reason)) => reason.clone(),
+            (None, None) => String::new(),
+        };
+        println!(
+            "{:<14}  {:<8}  {:<8}  {:<6}  {}",
+            check.mode.to_string(),
+            outcome(check.mode.should_succeed()),
+            outcome(check.succeeded),
+            if check.passed() { "✅" } else { "❌" },
+            detail
+        );
+    }
+
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    if failed > 0 {
+        return Err(CapacitorError::SelfTestFailed {
+            failed,
+            total: checks.len(),
+        }
+        .into());
+    }
+    println!("\n✅ All modes behaved as expected\n");
+    Ok(())
+}
+
+async fn reconcile_cmd(
+    config: &Config,
+    amount: &str,
+    token: Option<&str>,
+    sweep_surplus: bool,
+    yes: bool,
+) -> Result<()> {
+    println!("\n🧮 Reconcile Contract Funding\n");
+    println!("{}", "=".repeat(60));
+
+    let Some(contract) = config.deployed_contract else {
+        println!("\n❌ Error: Invalid contract address!");
+        println!("\nPlease set deployed_contract in capacitor.toml");
+        return Err(CapacitorError::Config("deployed_contract is not set".into()).into());
+    };
+    let token = match token {
+        Some(token) => TokenBook::from_config(config).resolve(config.network, token)?,
+        None => config.token,
+    };
+
+    let provider = Arc::new(read_only(config)?);
+    let (symbol, decimals) = token_info(provider.clone(), token, config).await?;
+    let next_amount: U256 = parse_units(amount, decimals)?.into();
+    let fee = FeeResolver::new(provider.clone(), token)
+        .fee_info_or_fallback(config.flash_loan_provider, token, next_amount)
+        .await?;
+    let report = reconcile(provider, contract, token, fee.absolute).await?;
+
+    println!("\n📍 Network: {}", config.network);
+    println!("📄 Contract: {:?}", contract);
+    println!(
+        "\n💰 Balance: {} {}",
+        format_units(report.balance, decimals)?,
+        symbol
+    );
+    println!(
+        "📋 Next loan fee: {} {} ({} bps, for {} {})",
+        format_units(report.min_fee, decimals)?,
+        symbol,
+        fee.bps,
+        format_units(next_amount, decimals)?,
+        symbol
+    );
+    if report.balance < report.min_fee {
+        println!(
+            "❌ Short by: {} {}",
+            format_units(report.min_fee - report.balance, decimals)?,
+            symbol
+        );
+    } else {
+        println!(
+            "📤 Surplus: {} {}",
+            format_units(report.surplus, decimals)?,
+            symbol
+        );
+    }
+
+    if !sweep_surplus || report.surplus.is_zero() {
+        println!();
+        return Ok(());
+    }
+    if config.owner_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key in capacitor.toml to sweep");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
+    }
+
+    let client = FlashLoanClient::connect(config).await?;
+    let summary = Summary::new(config.network, "Withdraw surplus to the owner")
+        .with("Contract", format!("{:?}", contract))
+        .with(
+            "Amount",
+            format!("{} {}", format_units(report.surplus, decimals)?, symbol),
+        )
+        .with("To", format!("{:?}", client.owner_address()));
+    if !confirm_send(&summary, yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    if let Some(receipt) = sweep(&client, contract, &report).await? {
+        println!("\n✅ Swept: {:?}", receipt.transaction_hash);
+    }
+    println!();
+    Ok(())
+}
+
+fn estimate_profit_cmd(amount: f64, buy: f64, sell: f64, gas: f64, fee_bps: u32) -> Result<()> {
+    let estimate = estimate_profit(amount, buy, sell, fee_bps, gas)?;
+
+    println!("\n📈 Profit Estimate\n");
+    println!("{}", "=".repeat(60));
+
+    println!("\n💰 Loan: {}", amount);
+    println!("🔁 Buy at {}, sell at {}", buy, sell);
+    println!("\n   Gross spread:   {:>+16.6}", estimate.gross_spread);
+    println!(
+        "   Flash loan fee: {:>16.6} ({} bps)",
+        estimate.flash_loan_fee, fee_bps
+    );
+    println!("   Gas:            {:>16.6}", estimate.gas_cost);
+    println!("   Net profit:     {:>+16.6}", estimate.net_profit);
+    println!(
+        "\n{}\n",
+        if estimate.profitable {
+            "✅ Profitable (before slippage)"
+        } else {
+            "❌ Not profitable"
+        }
+    );
+    Ok(())
+}
+
+async fn call(
+    config: &Config,
+    signature: &str,
+    args: &[String],
+    to: Option<Address>,
+    send: bool,
+    yes: bool,
+) -> Result<()> {
+    let Some(contract) = to.or(config.deployed_contract) else {
+        println!("\n❌ Error: No contract given!");
+        println!("\nPass --to 0x... or set deployed_contract in capacitor.toml");
+        return Err(CapacitorError::Config(
+            "no --to given and deployed_contract is not set".into(),
+        )
+        .into());
+    };
+    let function = parse_function(signature)?;
+    let tokens = parse_args(&function, args)?;
+
+    if !send {
+        let provider = read_only(config)?;
+        let outputs = call_raw(&provider, contract, signature, tokens).await?;
+        if outputs.is_empty() {
+            println!("(no return values)");
+        }
+        for (param, value) in function.outputs.iter().zip(&outputs) {
+            println!("{}: {}", param.kind, value);
+        }
+        return Ok(());
+    }
+
+    if config.owner_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key in capacitor.toml");
+        println!("   OR export OWNER_KEY=0x...your key...");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
+    }
+
+    let client = FlashLoanClient::connect(config).await?;
+    let summary = Summary::new(config.network, format!("Call {}", function.signature()))
+        .with("Contract", format!("{:?}", contract))
+        .with("Arguments", args.join(", "));
+    if !confirm_send(&summary, yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    println!("⏳ Sending {} to {:?}...", function.name, contract);
+    let receipt = send_raw(&client, contract, signature, tokens).await?;
+    let succeeded = receipt.status == Some(U64::from(1));
+
+    println!(
+        "{} {:?}",
+        if succeeded {
+            "✅ Mined"
+        } else {
+            "❌ Reverted"
+        },
+        receipt.transaction_hash
+    );
+    println!(
+        "   View: {}",
+        config.network.tx_url(receipt.transaction_hash)
+    );
+    if !succeeded {
+        return Err(CapacitorError::Reverted(receipt.transaction_hash).into());
+    }
+    Ok(())
+}
+
+async fn cancel_tx(config: &Config, tx_hash: H256, yes: bool) -> Result<()> {
+    println!("\n🛑 Cancel Transaction\n");
+    println!("{}", "=".repeat(60));
+
+    if config.owner_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key in capacitor.toml");
+        println!("   OR export OWNER_KEY=0x...your key...");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
+    }
+
+    let client = FlashLoanClient::connect(config).await?;
+
+    println!("\n📍 Network: {}", config.network);
+    println!("🔗 Transaction: {:?}", tx_hash);
+
+    let summary = Summary::new(config.network, "Cancel pending transaction")
+        .with("Transaction", format!("{:?}", tx_hash));
+    if !confirm_send(&summary, yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    println!("\n⏳ Sending replacement at the same nonce...");
+
+    match cancel(&client, tx_hash).await? {
+        CancelOutcome::Cancelled(receipt) => {
+            println!("✅ Cancelled by {:?}", receipt.transaction_hash);
+            println!(
+                "   View: {}",
+                config.network.tx_url(receipt.transaction_hash)
+            );
+        }
+        CancelOutcome::AlreadyMined(receipt) => {
+            let block = receipt
+                .block_number
+                .map(|block| block.to_string())
+                .unwrap_or_default();
+            println!("ℹ️  Already mined in block {}, nothing to cancel", block);
+            println!("   View: {}", config.network.tx_url(tx_hash));
+        }
+    }
+    Ok(())
+}
+
+async fn token_info<M: Middleware + 'static>(
+    client: Arc<M>,
+    token: Address,
+    config: &Config,
+) -> Result<(String, u32)>
+where
+    M::Error: 'static,
+{
+    let symbol = token_symbol(client.clone(), token).await;
+    let decimals = token_decimals(client, token, &config.decimals_override).await?;
+    Ok((symbol, u32::from(decimals)))
+}
+
+async fn transfer_ownership_cmd(config: &Config, new_owner: Address, yes: bool) -> Result<()> {
+    println!("\n🔑 Transfer Ownership\n");
+    println!("{}", "=".repeat(60));
+
+    let Some(contract) = config.deployed_contract else {
+        println!("\n❌ Error: Invalid contract address!");
+        println!("\nPlease set deployed_contract in capacitor.toml");
+        return Err(CapacitorError::Config("deployed_contract is not set".into()).into());
+    };
+    if config.owner_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key in capacitor.toml");
+        println!("   OR export OWNER_KEY=0x...your key...");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
+    }
+
+    let client = FlashLoanClient::connect(config).await?;
+
+    println!("\n📍 Network: {}", config.network);
+    println!("📄 Contract: {:?}", contract);
+    println!("👤 Current owner: {:?}", client.owner_address());
+    println!("➡️  New owner: {:?}", new_owner);
+
+    let summary = Summary::new(config.network, "Transfer contract ownership")
+        .with("Contract", format!("{:?}", contract))
+        .with("From", format!("{:?}", client.owner_address()))
+        .with("To", format!("{:?}", new_owner));
+    println!("\n⚠️  Only the new owner's key can run loans or withdraw afterwards.");
+    if !confirm_irreversible(&summary, yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    println!("\n⏳ Transferring ownership...");
+    let receipt = transfer_ownership(&client, contract, new_owner).await?;
+    println!("✅ Owner is now {:?}", new_owner);
+    println!(
+        "   View: {}\n",
+        config.network.tx_url(receipt.transaction_hash)
+    );
+    Ok(())
+}
+
+fn print_event(
+    network: Network,
+    event: &FlashLoanExecuted,
+    symbol: &str,
+    decimals: u32,
+) -> Result<()> {
+    let block = event
+        .block_number
+        .map(|block| block.to_string())
+        .unwrap_or_else(|| "pending".to_string());
+
+    if event.removed {
+        println!("\n⚠️  Removed by reorg (block {})", block);
+    } else {
+        println!("\n⚡ Flash Loan (block {})", block);
+    }
+    println!(
+        "   Amount: {} {}",
+        format_units(event.amount, decimals)?,
+        symbol
+    );
+    println!("   Fee: {} {}", format_units(event.fee, decimals)?, symbol);
+    println!("   Initiator: {:?}", event.initiator);
+    if let Some(tx_hash) = event.tx_hash {
+        println!("   View: {}", network.tx_url(tx_hash));
+    }
+    Ok(())
+}
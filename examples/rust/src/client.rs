@@ -0,0 +1,204 @@
+//! Connection to Plasma holding one signer per role.
+//!
+//! The owner deploys the tester and initiates flash loans; the funder sends
+//! fee tokens to the contract. Both roles use the same key unless a separate
+//! `funder_key` is configured.
+
+use crate::{
+    config::Config,
+    error::CapacitorError,
+    failover::FailoverProvider,
+    gas::GasPolicy,
+    poll::PollPolicy,
+    rate_limit::RateLimiter,
+    record::{Playback, Recorder},
+    relay::{RelaySubmitter, SubmitMode},
+    signer::{BoxedSigner, CapacitorSigner},
+    tx::ResolvedTxType,
+};
+use async_trait::async_trait;
+use ethers::{prelude::*, types::transaction::eip2718::TypedTransaction};
+use eyre::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Debug, str::FromStr, sync::Arc};
+
+/// Transport stack every request goes through.
+pub type Transport = Recorder<RateLimiter<Endpoint>>;
+pub type SignerClient = SignerMiddleware<Provider<Transport>, BoxedSigner>;
+
+#[derive(Debug, Clone)]
+pub struct FlashLoanClient {
+    pub provider: Provider<Transport>,
+    pub chain_id: U256,
+    /// Transaction type for every send, resolved once on connect.
+    pub tx_type: ResolvedTxType,
+    pub gas: GasPolicy,
+    /// Native balance a sender must hold, overriding the estimated cost.
+    pub min_native_balance: Option<U256>,
+    pub poll: PollPolicy,
+    /// Set when transactions go to a private relay instead of the RPC.
+    pub relay: Option<RelaySubmitter>,
+    /// Where the outcome of every flash loan is POSTed, when set.
+    pub webhook_url: Option<String>,
+    pub owner: Arc<SignerClient>,
+    pub funder: Arc<SignerClient>,
+}
+
+impl FlashLoanClient {
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let owner_key = config
+            .owner_key()
+            .ok_or_else(|| CapacitorError::Config("owner key is not configured".into()))?;
+        let funder_key = config
+            .funder_key()
+            .ok_or_else(|| CapacitorError::Config("funder key is not valid".into()))?;
+        let owner: LocalWallet = owner_key.parse()?;
+        let funder: LocalWallet = funder_key.parse()?;
+        Self::connect_with(config, owner, funder).await
+    }
+
+    /// Connect with the given signers instead of the configured keys, e.g.
+    /// to sign through a KMS.
+    pub async fn connect_with(
+        config: &Config,
+        owner: impl CapacitorSigner + 'static,
+        funder: impl CapacitorSigner + 'static,
+    ) -> Result<Self> {
+        let provider = read_only(config)?;
+        let chain_id = provider.get_chainid().await?;
+        if chain_id != config.network.chain_id().into() {
+            return Err(CapacitorError::NetworkMismatch {
+                network: config.network,
+                actual: chain_id.low_u64(),
+            }
+            .into());
+        }
+        let tx_type = config.tx_type.resolve(&provider).await;
+
+        let relay = match (config.submit, config.relay_url.as_deref()) {
+            (SubmitMode::Public, _) => None,
+            (SubmitMode::Private, Some(url)) => Some(RelaySubmitter::new(url)?),
+            (SubmitMode::Private, None) => {
+                return Err(CapacitorError::Config(
+                    "submit = \"private\" needs relay_url to be set".into(),
+                )
+                .into())
+            }
+        };
+
+        let owner = signer(&provider, owner, chain_id);
+        let funder = signer(&provider, funder, chain_id);
+
+        Ok(Self {
+            provider,
+            chain_id,
+            tx_type,
+            gas: config.gas_policy(),
+            min_native_balance: config.min_native_balance()?,
+            poll: config.poll_policy(),
+            relay,
+            webhook_url: config.webhook_url.clone(),
+            owner,
+            funder,
+        })
+    }
+
+    pub fn owner_address(&self) -> Address {
+        self.owner.address()
+    }
+
+    pub fn funder_address(&self) -> Address {
+        self.funder.address()
+    }
+
+    /// Sign `tx` with `signer` and submit it as configured: to the RPC
+    /// endpoints, or to the private relay. Either way the returned pending
+    /// transaction is watched on the RPC endpoints.
+    pub async fn send_transaction(
+        &self,
+        signer: &SignerClient,
+        tx: TypedTransaction,
+    ) -> Result<PendingTransaction<'_, Transport>> {
+        let hash = match &self.relay {
+            None => signer.send_transaction(tx, None).await?.tx_hash(),
+            Some(relay) => relay.submit(signer, tx).await?,
+        };
+        Ok(PendingTransaction::new(hash, &self.provider))
+    }
+
+    /// True when fee funding is paid by a different account than the owner.
+    pub fn has_separate_funder(&self) -> bool {
+        self.owner_address() != self.funder_address()
+    }
+}
+
+/// Where requests end up: live nodes or a recorded session.
+#[derive(Debug, Clone)]
+pub enum Endpoint {
+    Http(FailoverProvider<Http>),
+    Playback(Arc<Playback>),
+}
+
+impl Endpoint {
+    /// The live endpoints, unless a recorded session is being replayed.
+    pub fn failover(&self) -> Option<&FailoverProvider<Http>> {
+        match self {
+            Endpoint::Http(http) => Some(http),
+            Endpoint::Playback(_) => None,
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for Endpoint {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Endpoint::Http(http) => JsonRpcClient::request(http, method, params).await,
+            Endpoint::Playback(playback) => {
+                JsonRpcClient::request(playback.as_ref(), method, params).await
+            }
+        }
+    }
+}
+
+/// The failover list underneath `provider`, for health statistics.
+pub fn failover(provider: &Provider<Transport>) -> Option<&FailoverProvider<Http>> {
+    provider.as_ref().inner().inner().failover()
+}
+
+/// A provider without signers, for read-only commands that need no keys.
+pub fn read_only(config: &Config) -> Result<Provider<Transport>> {
+    Ok(Provider::new(transport(config)?).interval(config.poll_policy().interval))
+}
+
+fn transport(config: &Config) -> Result<Transport> {
+    let limited = match &config.rpc.replay {
+        // A replay never hits the network, so there is nothing to throttle
+        Some(path) => RateLimiter::new(Endpoint::Playback(Arc::new(Playback::load(path)?)), 0),
+        None => {
+            let endpoints = config
+                .rpc_endpoints()?
+                .into_iter()
+                .map(|url| Ok((url.to_string(), Http::from_str(url)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let http = FailoverProvider::new(endpoints);
+            RateLimiter::new(Endpoint::Http(http), config.rpc.rate_limit)
+        }
+    };
+    Ok(Recorder::new(limited, config.rpc.record.clone()))
+}
+
+fn signer(
+    provider: &Provider<Transport>,
+    signer: impl CapacitorSigner + 'static,
+    chain_id: U256,
+) -> Arc<SignerClient> {
+    let signer = BoxedSigner::new(signer, chain_id.as_u64());
+    Arc::new(SignerMiddleware::new(provider.clone(), signer))
+}
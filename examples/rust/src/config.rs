@@ -0,0 +1,324 @@
+//! Runtime configuration shared by the example binaries.
+//!
+//! Settings are read from `capacitor.toml` in the working directory (or the
+//! file named by `CAPACITOR_CONFIG`). Anything left out falls back to the
+//! Plasma testnet defaults below. Private keys and the deployed contract
+//! address can also be supplied through the environment so they never have
+//! to be written to disk.
+
+use crate::{
+    error::CapacitorError,
+    gas::{GasPolicy, DEFAULT_GAS_BUFFER},
+    network::Network,
+    poll::{PollPolicy, DEFAULT_MAX_POLL_DURATION, DEFAULT_POLL_INTERVAL},
+    relay::SubmitMode,
+    secret::Redacted,
+    tx::TxType,
+};
+use ethers::{
+    types::{Address, U256},
+    utils::{parse_ether, parse_units},
+};
+use eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// Plasma testnet defaults
+pub const PLASMA_RPC: &str = "https://testnet-rpc.plasma.to";
+pub const FLASH_LOAN_PROVIDER: &str = "0x63A6E3A5743F75388e58e8B778023380694aD3e5";
+pub const TUSDT_TOKEN: &str = "0xE5aE1FF9c761F581ac4F1d3075e12ae340500C99";
+
+pub const DEFAULT_CONFIG_FILE: &str = "capacitor.toml";
+pub const DEFAULT_OUTPUT_DIR: &str = "runs";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub network: Network,
+    pub rpc: RpcConfig,
+    /// Endpoints to fail over between, tried in order. Replaces `[rpc] url`;
+    /// can't be combined with `[rpc] urls`.
+    pub rpc_urls: Vec<String>,
+    pub flash_loan_provider: Address,
+    pub token: Address,
+    pub deployed_contract: Option<Address>,
+    /// Key of the contract owner. Deploys the tester and initiates loans.
+    pub owner_key: Option<Redacted<String>>,
+    /// Key of the account that pays fee tokens into the contract.
+    /// Defaults to the owner when not set.
+    pub funder_key: Option<Redacted<String>>,
+    /// XPL price used to show transaction costs in USD.
+    pub xpl_price_usd: Option<f64>,
+    /// How far (in bps) the charged fee may exceed the expected fee before
+    /// the run fails.
+    pub max_fee_tolerance: u32,
+    /// Extra token symbols per network, e.g. `[tokens.plasma-testnet]`.
+    pub tokens: HashMap<String, BTreeMap<String, Address>>,
+    /// Decimals to use for tokens whose `decimals()` call reverts.
+    pub decimals_override: HashMap<Address, u8>,
+    /// Tester artifact. When unset, Foundry's `out/` and then Hardhat's
+    /// `artifacts/` are searched.
+    pub artifact: Option<PathBuf>,
+    /// `legacy`, `eip1559`, or `auto` to probe the node on connect.
+    pub tx_type: TxType,
+    /// Fixed gas limit for every transaction, skipping estimation.
+    pub gas_limit: Option<u64>,
+    /// Multiplier applied to gas estimates.
+    pub gas_buffer: f64,
+    /// Gas limit to send with when estimation fails, instead of aborting.
+    pub fallback_gas_limit: Option<u64>,
+    /// XPL a sending wallet must hold. When unset, the estimated cost of
+    /// the transaction at the current gas price is required instead.
+    pub min_native_balance: Option<f64>,
+    /// Largest loan a run may request, in whole tokens of the borrowed
+    /// token. A guardrail of your own, separate from pool liquidity.
+    pub max_loan_amount: Option<f64>,
+    /// POST the outcome of every flash loan here as JSON.
+    pub webhook_url: Option<String>,
+    /// Directory `--save` writes run records to.
+    pub output_dir: PathBuf,
+    /// Seconds between receipt lookups while waiting for a transaction.
+    pub poll_interval: f64,
+    /// Seconds to wait for a receipt before failing with a timeout.
+    pub max_poll_duration: f64,
+    /// Blocks to wait for, including the one a transaction is mined in.
+    pub confirmations: usize,
+    /// `public` to send through the RPC endpoints, `private` for `relay_url`.
+    pub submit: SubmitMode,
+    /// Private relay accepting `eth_sendRawTransaction`.
+    pub relay_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RpcConfig {
+    pub url: String,
+    /// Endpoints to fail over between, tried in order. Replaces `url` when
+    /// set.
+    pub urls: Vec<String>,
+    /// Maximum JSON-RPC requests per second. `0` disables rate limiting.
+    pub rate_limit: u32,
+    /// WebSocket endpoint used for live event subscriptions.
+    pub ws_url: Option<String>,
+    /// Write every request and response to this file.
+    pub record: Option<PathBuf>,
+    /// Serve responses from a recorded session instead of the network.
+    pub replay: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            network: Network::default(),
+            rpc: RpcConfig::default(),
+            rpc_urls: Vec::new(),
+            flash_loan_provider: FLASH_LOAN_PROVIDER.parse().expect("valid provider address"),
+            token: TUSDT_TOKEN.parse().expect("valid token address"),
+            deployed_contract: None,
+            owner_key: None,
+            funder_key: None,
+            xpl_price_usd: None,
+            max_fee_tolerance: 0,
+            tokens: HashMap::new(),
+            decimals_override: HashMap::new(),
+            artifact: None,
+            tx_type: TxType::default(),
+            gas_limit: None,
+            gas_buffer: DEFAULT_GAS_BUFFER,
+            fallback_gas_limit: None,
+            min_native_balance: None,
+            max_loan_amount: None,
+            webhook_url: None,
+            output_dir: PathBuf::from(DEFAULT_OUTPUT_DIR),
+            poll_interval: DEFAULT_POLL_INTERVAL.as_secs_f64(),
+            max_poll_duration: DEFAULT_MAX_POLL_DURATION.as_secs_f64(),
+            confirmations: 1,
+            submit: SubmitMode::default(),
+            relay_url: None,
+        }
+    }
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            url: PLASMA_RPC.to_string(),
+            urls: Vec::new(),
+            rate_limit: 0,
+            ws_url: None,
+            record: None,
+            replay: None,
+        }
+    }
+}
+
+impl RpcConfig {
+    /// Apply `--record` and `--replay` from the command line.
+    ///
+    /// A flag that is given replaces `record` or `replay`; one that isn't
+    /// leaves the configured value in place.
+    pub fn override_session(&mut self, record: Option<PathBuf>, replay: Option<PathBuf>) {
+        if let Some(path) = record {
+            self.record = Some(path);
+        }
+        if let Some(path) = replay {
+            self.replay = Some(path);
+        }
+    }
+
+    /// HTTP endpoints in failover order.
+    pub fn endpoints(&self) -> Vec<&str> {
+        if self.urls.is_empty() {
+            vec![self.url.as_str()]
+        } else {
+            self.urls.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+impl Config {
+    /// Load the config file (if present) and apply environment overrides.
+    ///
+    /// A `.env` file in the working directory is read first; variables
+    /// already set in the environment take precedence over it. Recognised
+    /// variables: `OWNER_KEY` (or `PRIVATE_KEY`), `FUNDER_KEY`
+    /// and `DEPLOYED_CONTRACT`.
+    pub fn load() -> Result<Self> {
+        dotenvy::dotenv().ok();
+        let path = env::var("CAPACITOR_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_FILE.to_string());
+        let mut config = if Path::new(&path).exists() {
+            Self::from_file(&path)?
+        } else {
+            Self::default()
+        };
+
+        if let Ok(key) = env::var("OWNER_KEY").or_else(|_| env::var("PRIVATE_KEY")) {
+            config.owner_key = Some(Redacted::new(key));
+        }
+        if let Ok(key) = env::var("FUNDER_KEY") {
+            config.funder_key = Some(Redacted::new(key));
+        }
+        if let Ok(address) = env::var("DEPLOYED_CONTRACT") {
+            let address = address
+                .parse()
+                .map_err(|_| eyre!("DEPLOYED_CONTRACT is not a valid address: {}", address))?;
+            config.deployed_contract = Some(address);
+        }
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&contents).wrap_err_with(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// HTTP endpoints in failover order.
+    ///
+    /// `rpc_urls` and `[rpc] urls` are the same list; setting both is a
+    /// config error rather than one silently winning.
+    pub fn rpc_endpoints(&self) -> Result<Vec<&str>, CapacitorError> {
+        match (self.rpc_urls.is_empty(), self.rpc.urls.is_empty()) {
+            (false, false) => Err(CapacitorError::Config(
+                "set either rpc_urls or [rpc] urls, not both".into(),
+            )),
+            (false, true) => Ok(self.rpc_urls.iter().map(String::as_str).collect()),
+            (true, _) => Ok(self.rpc.endpoints()),
+        }
+    }
+
+    pub fn gas_policy(&self) -> GasPolicy {
+        GasPolicy {
+            limit: self.gas_limit,
+            buffer: self.gas_buffer,
+            fallback: self.fallback_gas_limit,
+        }
+    }
+
+    /// `min_native_balance` in wei.
+    pub fn min_native_balance(&self) -> Result<Option<U256>, CapacitorError> {
+        self.min_native_balance
+            .map(|xpl| {
+                parse_ether(xpl).map_err(|e| {
+                    CapacitorError::Config(format!("invalid min_native_balance {}: {}", xpl, e))
+                })
+            })
+            .transpose()
+    }
+
+    /// `max_loan_amount` in base units of a token with `decimals`.
+    pub fn max_loan_amount(&self, decimals: u8) -> Result<Option<U256>, CapacitorError> {
+        self.max_loan_amount
+            .map(|cap| {
+                parse_units(cap.to_string(), u32::from(decimals))
+                    .map(Into::into)
+                    .map_err(|e| {
+                        CapacitorError::Config(format!("invalid max_loan_amount {}: {}", cap, e))
+                    })
+            })
+            .transpose()
+    }
+
+    pub fn poll_policy(&self) -> PollPolicy {
+        PollPolicy {
+            interval: Duration::from_secs_f64(self.poll_interval),
+            max_duration: Duration::from_secs_f64(self.max_poll_duration),
+            confirmations: self.confirmations.max(1),
+        }
+    }
+
+    /// The owner key, if one has been configured with a plausible value.
+    pub fn owner_key(&self) -> Option<&str> {
+        self.owner_key
+            .as_ref()
+            .map(|key| key.expose().as_str())
+            .filter(|key| is_valid_key(key))
+    }
+
+    /// The funder key, falling back to the owner key.
+    pub fn funder_key(&self) -> Option<&str> {
+        match self.funder_key.as_ref().map(|key| key.expose().as_str()) {
+            Some(key) if is_valid_key(key) => Some(key),
+            Some(_) => None,
+            None => self.owner_key(),
+        }
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    key != "<YOUR_PRIVATE_KEY_HERE>" && key.starts_with("0x")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_paths_from_the_file_survive_absent_flags() {
+        let path = env::temp_dir().join(format!("capacitor-config-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "[rpc]\nrecord = \"session.json\"\nreplay = \"old.json\"\n",
+        )
+        .unwrap();
+        env::set_var("CAPACITOR_CONFIG", &path);
+        let mut config = Config::load().unwrap();
+        env::remove_var("CAPACITOR_CONFIG");
+        fs::remove_file(&path).unwrap();
+
+        config.rpc.override_session(None, Some("new.json".into()));
+        assert_eq!(config.rpc.record, Some(PathBuf::from("session.json")));
+        assert_eq!(config.rpc.replay, Some(PathBuf::from("new.json")));
+
+        config.rpc.override_session(Some("other.json".into()), None);
+        assert_eq!(config.rpc.record, Some(PathBuf::from("other.json")));
+        assert_eq!(config.rpc.replay, Some(PathBuf::from("new.json")));
+    }
+}
@@ -0,0 +1,76 @@
+//! Confirmation before sending on mainnet.
+//!
+//! Every state-changing command shows a summary and waits for the user to
+//! type `yes` when it would send on a non-test network. Testnets never
+//! prompt. `--yes`, or stdin not being a terminal, skips the prompt so
+//! scripts and CI keep working. Sends that can't be undone prompt on every
+//! network.
+
+use crate::network::Network;
+use eyre::Result;
+use std::{
+    fmt,
+    io::{self, BufRead, IsTerminal, Write},
+};
+
+/// What is about to be sent, shown before asking.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub network: Network,
+    pub action: String,
+    pub details: Vec<(String, String)>,
+}
+
+impl Summary {
+    pub fn new(network: Network, action: impl Into<String>) -> Self {
+        Self {
+            network,
+            action: action.into(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, label: impl Into<String>, value: impl fmt::Display) -> Self {
+        self.details.push((label.into(), value.to_string()));
+        self
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "   Network: {}", self.network)?;
+        writeln!(f, "   Action: {}", self.action)?;
+        for (label, value) in &self.details {
+            writeln!(f, "   {}: {}", label, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Print `summary` and ask for `yes` on stdin. Anything else declines.
+pub fn confirm(summary: &Summary) -> Result<bool> {
+    println!("\n⚠️  About to send on {}:", summary.network);
+    print!("{}", summary);
+    print!("\nType `yes` to continue: ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim() == "yes")
+}
+
+/// Ask only when it matters: on mainnet, without `--yes`, at a terminal.
+pub fn confirm_send(summary: &Summary, yes: bool) -> Result<bool> {
+    if summary.network.is_testnet() || yes || !io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    confirm(summary)
+}
+
+/// Like [`confirm_send`], but asks on testnets too.
+pub fn confirm_irreversible(summary: &Summary, yes: bool) -> Result<bool> {
+    if yes || !io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    confirm(summary)
+}
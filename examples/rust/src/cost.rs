@@ -0,0 +1,55 @@
+//! Transaction cost in native XPL and, optionally, USD.
+
+use ethers::{
+    types::{TransactionReceipt, U256},
+    utils::format_ether,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostReport {
+    pub gas_used: U256,
+    pub effective_gas_price: U256,
+    /// `gas_used * effective_gas_price`, in wei.
+    pub cost: U256,
+    pub cost_usd: Option<f64>,
+}
+
+/// Work out what a mined transaction cost.
+///
+/// Nodes that omit `effectiveGasPrice` from receipts report a zero cost.
+pub fn cost_report(receipt: &TransactionReceipt, xpl_price_usd: Option<f64>) -> CostReport {
+    CostReport::estimated(
+        receipt.gas_used.unwrap_or_default(),
+        receipt.effective_gas_price.unwrap_or_default(),
+        xpl_price_usd,
+    )
+}
+
+impl CostReport {
+    /// What a transaction using up to `gas` would cost at `gas_price`, for
+    /// showing before it is sent.
+    pub fn estimated(gas: U256, gas_price: U256, xpl_price_usd: Option<f64>) -> Self {
+        let cost = gas.saturating_mul(gas_price);
+        CostReport {
+            gas_used: gas,
+            effective_gas_price: gas_price,
+            cost,
+            cost_usd: xpl_price_usd.map(|price| cost_in_xpl(cost) * price),
+        }
+    }
+}
+
+fn cost_in_xpl(cost: U256) -> f64 {
+    format_ether(cost).parse().unwrap_or_default()
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} XPL", format_ether(self.cost))?;
+        if let Some(usd) = self.cost_usd {
+            write!(f, " (~${:.4})", usd)?;
+        }
+        Ok(())
+    }
+}
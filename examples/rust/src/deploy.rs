@@ -3,116 +3,215 @@
 // This program deploys your flash loan receiver contract.
 // You only need to do this ONCE, then reuse the deployed address.
 
+use clap::Parser;
 use ethers::{
     prelude::*,
     utils::format_ether,
 };
 use eyre::Result;
-use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+use std::{path::PathBuf, process};
+use flashloan_example::{
+    confirm::{confirm_send, Summary},
+    error::exit_code,
+    deployment::{
+        check_deploy_address, deploy_tester, load_tester_artifact, predict_next_deploy_address,
+        tester_deploy_tx, verify_deployment,
+    },
+    preflight::check_gas_funds,
+    send::TxBuilder,
+    CapacitorError, Config, FlashLoanClient,
+};
+
+#[derive(Parser)]
+#[command(about = "Deploy the FlashLoanTester contract")]
+struct Args {
+    /// Estimate the deployment cost without deploying
+    #[arg(long)]
+    dry_run: bool,
 
-// Configuration
-const PLASMA_RPC: &str = "https://testnet-rpc.plasma.to";
-const FLASH_LOAN_PROVIDER: &str = "0x63A6E3A5743F75388e58e8B778023380694aD3e5";
-const PRIVATE_KEY: &str = "<YOUR_PRIVATE_KEY_HERE>";
+    /// Skip the mainnet confirmation prompt
+    #[arg(long)]
+    yes: bool,
 
-// Contract bytecode and ABI (compile with hardhat first)
-// Note: This is a placeholder - load from artifacts in production
-const CONTRACT_BYTECODE: &str = "0x..."; // Load from artifacts
+    /// Record every JSON-RPC request and response to this file
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Replay a recorded session instead of talking to the network
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        process::exit(exit_code(&e));
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = Args::parse();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+
     println!("\n🚀 Flash Loan Contract Deployment\n");
     println!("{}", "=".repeat(60));
 
-    // Validate private key
-    if PRIVATE_KEY == "<YOUR_PRIVATE_KEY_HERE>" || !PRIVATE_KEY.starts_with("0x") {
+    let mut config = Config::load().map_err(|e| CapacitorError::Config(format!("{:#}", e)))?;
+    config.rpc.override_session(args.record.clone(), args.replay.clone());
+
+    // Validate private key (the deployer becomes the contract owner)
+    if config.owner_key().is_none() {
         println!("\n❌ Error: Invalid private key!");
-        println!("\nPlease update PRIVATE_KEY in src/deploy.rs:");
-        println!("   const PRIVATE_KEY: &str = \"0x...your key...\";");
-        return Ok(());
+        println!("\nPlease set owner_key in capacitor.toml:");
+        println!("   owner_key = \"0x...your key...\"");
+        println!("   OR export OWNER_KEY=0x...your key...");
+        return Err(CapacitorError::Config("owner key is not valid".into()).into());
     }
 
-    // Setup provider
-    let provider = Provider::<Http>::try_from(PLASMA_RPC)?;
-    let chain_id = provider.get_chainid().await?;
-
-    println!("\n📍 Network: Plasma Testnet");
-    println!("📡 Chain ID: {}", chain_id);
-
-    // Setup wallet
-    let wallet: LocalWallet = PRIVATE_KEY.parse()?;
-    let wallet = wallet.with_chain_id(chain_id.as_u64());
-    let address = wallet.address();
+    // Setup provider and wallet
+    let client = FlashLoanClient::connect(&config).await?;
+    let address = client.owner_address();
 
+    println!("\n📍 Network: {}", config.network);
+    println!("📡 Chain ID: {}", client.chain_id);
     println!("👤 Deployer: {:?}", address);
+    println!("🏦 Flash Loan Provider: {:?}", config.flash_loan_provider);
+    println!("🧾 Transaction Type: {}", client.tx_type);
+    if let Some(relay) = &client.relay {
+        println!("🔒 Submission: private relay {}", relay.url());
+    }
 
     // Check balance
-    let balance = provider.get_balance(address, None).await?;
+    let balance = client.provider.get_balance(address, None).await?;
     println!("💰 Balance: {} XPL", format_ether(balance));
 
-    if balance.is_zero() {
-        println!("\n❌ Error: No XPL for gas!");
+    if args.dry_run {
+        return dry_run(&client, &config, balance).await;
+    }
+
+    let artifact = match load_tester_artifact(config.artifact.as_deref()) {
+        Ok(artifact) => artifact,
+        Err(e) => {
+            println!("\n❌ Error: {}", e);
+            println!("\nCompile the contract with Hardhat or Foundry first:");
+            println!("   cd ../.. && npx hardhat compile   (or: forge build)");
+            println!("\nOr deploy with the Node.js or Python scripts:");
+            println!("   cd ../nodejs && node 1-deploy-contract.cjs");
+            println!("   cd ../python && python3 1_deploy_contract.py\n");
+            return Err(CapacitorError::Config(e.to_string()).into());
+        }
+    };
+
+    // Priced as it will be sent, with the buffered gas limit
+    let deploy = tester_deploy_tx(&client, &artifact, config.flash_loan_provider)?;
+    let (gas, cost) =
+        TxBuilder::new(&client, &client.owner, deploy).estimate_limit_and_cost().await?;
+
+    if let Err(e) = check_gas_funds(&client, address, cost).await {
+        println!("\n❌ Error: {}", e);
         println!("   Get XPL from: https://gas.zip/faucet/plasma");
-        return Ok(());
+        return Err(e);
     }
 
-    // Create client
-    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+    if !config.network.is_testnet() {
+        let summary = Summary::new(config.network, "Deploy FlashLoanTester")
+            .with("Flash Loan Provider", format!("{:?}", config.flash_loan_provider))
+            .with("Gas Limit", gas)
+            .with("Estimated Cost", format!("{} XPL", format_ether(cost)));
+        if !confirm_send(&summary, args.yes)? {
+            println!("\n❌ Aborted; nothing was sent.\n");
+            return Ok(());
+        }
+    }
 
     println!("\n{}", "=".repeat(60));
     println!("Deploying FlashLoanTester Contract...");
     println!("{}", "=".repeat(60));
 
-    println!("\n⏳ Deploying contract...");
+    let predicted = predict_next_deploy_address(&client).await?;
+    println!("\n🔮 Predicted address: {:?}", predicted);
 
-    // NOTE: In production, you would:
-    // 1. Load ABI and bytecode from Hardhat artifacts
-    // 2. Use ethers-rs contract deployment
-    // 3. Wait for deployment confirmation
-    //
-    // Example:
-    // let factory = ContractFactory::new(abi, bytecode, client);
-    // let contract = factory.deploy(constructor_args)?.send().await?;
-    // let address = contract.address();
+    println!("\n⏳ Deploying contract...");
 
-    println!("
-⚠️  IMPORTANT: Contract Deployment in Rust
+    let contract_address = deploy_tester(&client, artifact, config.flash_loan_provider).await?;
 
-To deploy contracts in Rust, you need to:
+    println!("✅ Deployed at: {:?}", contract_address);
+    println!("   View: {}", config.network.address_url(contract_address));
 
-1. Compile the contract with Hardhat:
-   cd ../..
-   npx hardhat compile
+    if let Err(e) = check_deploy_address(predicted, contract_address) {
+        println!("\n❌ {}", e);
+        println!("   Another transaction from the deployer used the nonce first.");
+        println!("   The contract is at the deployed address above; check it before use.");
+        return Err(e.into());
+    }
 
-2. Load the ABI and bytecode from artifacts:
-   let abi_json = include_str!(\"../../artifacts/contracts/FlashLoanTester.sol/FlashLoanTester.json\");
-   let contract_json: serde_json::Value = serde_json::from_str(abi_json)?;
+    println!("\n🔍 Verifying on-chain wiring...");
+
+    if let Err(e) = verify_deployment(
+        client.owner.clone(),
+        contract_address,
+        config.flash_loan_provider,
+        address,
+    )
+    .await
+    {
+        println!("\n❌ Deployment check failed: {}", e);
+        println!("   Do not use this contract; fix flash_loan_provider and redeploy.");
+        return Err(e);
+    }
 
-3. Create factory and deploy:
-   let factory = ContractFactory::new(abi, bytecode, client);
-   let contract = factory
-       .deploy(flash_loan_provider)?
-       .send()
-       .await?;
+    println!("✅ Owner: {:?}", address);
+    println!("✅ Flash Loan Provider: {:?}", config.flash_loan_provider);
 
-4. Get deployed address:
-   let address = contract.address();
+    println!("\n{}", "=".repeat(60));
+    println!("✨ Deployment Complete!");
+    println!("{}", "=".repeat(60));
 
-For a complete working example, use the Node.js or Python deployment scripts:
-   cd ../nodejs && node 1-deploy-contract.cjs
-   cd ../python && python3 1_deploy_contract.py
+    println!("\nSet the address in capacitor.toml:");
+    println!("   deployed_contract = \"{:?}\"\n", contract_address);
 
-Then use the deployed address in the Rust execution script.
-");
+    Ok(())
+}
 
+async fn dry_run(client: &FlashLoanClient, config: &Config, balance: U256) -> Result<()> {
     println!("\n{}", "=".repeat(60));
-    println!("📝 Recommended: Use Node.js or Python for deployment");
+    println!("Dry Run: Estimating Deployment Cost");
     println!("{}", "=".repeat(60));
 
-    println!("\nDeployment options:");
-    println!("   1. cd ../nodejs && node 1-deploy-contract.cjs");
-    println!("   2. cd ../python && python3 1_deploy_contract.py");
-    println!("\nThen use the address in src/execute.rs\n");
+    let artifact = match load_tester_artifact(config.artifact.as_deref()) {
+        Ok(artifact) => artifact,
+        Err(e) => {
+            println!("\n❌ Error: {}", e);
+            println!("\nCompile the contract first:");
+            println!("   cd ../.. && npx hardhat compile   (or: forge build)");
+            return Err(CapacitorError::Config(e.to_string()).into());
+        }
+    };
+
+    let deploy = tester_deploy_tx(client, &artifact, config.flash_loan_provider)?;
+    let (gas, cost) =
+        TxBuilder::new(client, &client.owner, deploy).estimate_limit_and_cost().await?;
+
+    println!("\n🔮 Predicted address: {:?}", predict_next_deploy_address(client).await?);
+    println!("⛽ Gas Limit: {}", gas);
+    println!("💸 Estimated Cost: {} XPL", format_ether(cost));
+    println!("💰 Balance: {} XPL", format_ether(balance));
+
+    let need = client.min_native_balance.unwrap_or(cost);
+    if balance < need {
+        println!("\n⚠️  Warning: Balance is too low to cover the deployment!");
+        println!("   Short by: {} XPL", format_ether(need - balance));
+        println!("   Get XPL from: https://gas.zip/faucet/plasma");
+    } else {
+        println!("\n✅ Balance covers the deployment");
+    }
 
+    println!("\nNo transaction was sent.\n");
     Ok(())
 }
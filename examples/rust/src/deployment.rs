@@ -0,0 +1,580 @@
+//! Contract deployment helpers.
+
+use crate::{
+    abi::IFlashLoanTester, client::FlashLoanClient, error::CapacitorError, send::TxBuilder,
+};
+use ethers::{
+    abi::{Abi, ParamType, Token},
+    contract::ContractFactory,
+    providers::Middleware,
+    types::{transaction::eip2718::TypedTransaction, Address, BlockNumber, Bytes, Selector, U256},
+    utils::get_contract_address,
+};
+use eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Root of the Hardhat/Foundry project, relative to `examples/rust`.
+pub const PROJECT_ROOT: &str = "../..";
+
+/// Contract name of the tester, as both toolchains name its artifact.
+pub const TESTER_NAME: &str = "FlashLoanTester";
+
+/// ABI and creation bytecode of a compiled contract.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub abi: Abi,
+    pub bytecode: Bytes,
+    /// Runtime bytecode, when the artifact includes it.
+    pub deployed: Option<DeployedBytecode>,
+}
+
+/// Runtime bytecode as compiled, before the constructor has run.
+#[derive(Debug, Clone, Default)]
+pub struct DeployedBytecode {
+    pub code: Bytes,
+    /// Byte ranges the constructor fills with immutable values. Only
+    /// Foundry artifacts record them.
+    pub immutables: Vec<Range<usize>>,
+}
+
+// Hardhat stores `bytecode` as a hex string, Foundry as `{ "object": ... }`
+#[derive(Deserialize)]
+struct RawArtifact {
+    abi: Abi,
+    bytecode: RawBytecode,
+    #[serde(default, rename = "deployedBytecode")]
+    deployed_bytecode: Option<RawBytecode>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawBytecode {
+    Hardhat(Bytes),
+    Foundry {
+        object: Bytes,
+        #[serde(default, rename = "immutableReferences")]
+        immutable_references: HashMap<String, Vec<ImmutableReference>>,
+    },
+}
+
+#[derive(Deserialize)]
+struct ImmutableReference {
+    start: usize,
+    length: usize,
+}
+
+impl From<RawBytecode> for DeployedBytecode {
+    fn from(raw: RawBytecode) -> Self {
+        match raw {
+            RawBytecode::Hardhat(code) => Self {
+                code,
+                immutables: Vec::new(),
+            },
+            RawBytecode::Foundry {
+                object,
+                immutable_references,
+            } => Self {
+                code: object,
+                immutables: immutable_references
+                    .into_values()
+                    .flatten()
+                    .map(|r| r.start..r.start + r.length)
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Finds compiled artifacts by contract name under a project root.
+///
+/// Foundry (`out/X.sol/X.json`) is tried before Hardhat
+/// (`artifacts/contracts/X.sol/X.json`); either shape is normalized into an
+/// [`Artifact`].
+#[derive(Debug, Clone)]
+pub struct ArtifactLoader {
+    root: PathBuf,
+}
+
+impl ArtifactLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Paths searched for `name`, in order.
+    pub fn candidates(&self, name: &str) -> Vec<PathBuf> {
+        let file = format!("{}.sol/{}.json", name, name);
+        vec![
+            self.root.join("out").join(&file),
+            self.root.join("artifacts/contracts").join(&file),
+        ]
+    }
+
+    pub fn load(&self, name: &str) -> Result<Artifact> {
+        let candidates = self.candidates(name);
+        match candidates.iter().find(|path| path.exists()) {
+            Some(path) => load_artifact(path),
+            None => Err(eyre!(
+                "no artifact for {} (looked in {})",
+                name,
+                candidates
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// Read a Hardhat or Foundry artifact file.
+pub fn load_artifact(path: impl AsRef<Path>) -> Result<Artifact> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .wrap_err_with(|| format!("failed to read artifact {}", path.display()))?;
+    let raw: RawArtifact = serde_json::from_str(&contents)
+        .wrap_err_with(|| format!("failed to parse artifact {}", path.display()))?;
+    let bytecode = match raw.bytecode {
+        RawBytecode::Hardhat(bytes) | RawBytecode::Foundry { object: bytes, .. } => bytes,
+    };
+    if bytecode.is_empty() {
+        return Err(eyre!("artifact {} has no bytecode", path.display()));
+    }
+    Ok(Artifact {
+        abi: raw.abi,
+        bytecode,
+        deployed: raw
+            .deployed_bytecode
+            .map(DeployedBytecode::from)
+            .filter(|deployed| !deployed.code.is_empty()),
+    })
+}
+
+/// The tester artifact: `path` when configured, otherwise whichever of the
+/// Foundry or Hardhat outputs exists in the project.
+pub fn load_tester_artifact(path: Option<&Path>) -> Result<Artifact> {
+    match path {
+        Some(path) => load_artifact(path),
+        None => ArtifactLoader::new(PROJECT_ROOT).load(TESTER_NAME),
+    }
+}
+
+/// The transaction deploying `bytecode` from the owner with `args` passed
+/// to the constructor.
+///
+/// The arguments are checked against the ABI constructor first, so a
+/// mismatch fails here instead of as a reverted deployment.
+pub fn deploy_tx(
+    client: &FlashLoanClient,
+    abi: Abi,
+    bytecode: Bytes,
+    args: Vec<Token>,
+) -> Result<TypedTransaction> {
+    check_constructor_args(&abi, &args)?;
+
+    let factory = ContractFactory::new(abi, bytecode, client.owner.clone());
+    Ok(factory.deploy_tokens(args)?.tx)
+}
+
+/// The transaction [`deploy_tester`] sends. Estimate it with [`TxBuilder`]
+/// to price the deployment with the gas limit it will actually have.
+pub fn tester_deploy_tx(
+    client: &FlashLoanClient,
+    artifact: &Artifact,
+    flash_loan_provider: Address,
+) -> Result<TypedTransaction> {
+    let args = vec![Token::Address(flash_loan_provider)];
+    deploy_tx(
+        client,
+        artifact.abi.clone(),
+        artifact.bytecode.clone(),
+        args,
+    )
+}
+
+/// Deploy `bytecode` from the owner with `args` passed to the constructor,
+/// checked as in [`deploy_tx`].
+pub async fn deploy_contract(
+    client: &FlashLoanClient,
+    abi: Abi,
+    bytecode: Bytes,
+    args: Vec<Token>,
+) -> Result<Address> {
+    let tx = deploy_tx(client, abi, bytecode, args)?;
+    let receipt = TxBuilder::new(client, &client.owner, tx)
+        .send_and_confirm()
+        .await?;
+    receipt
+        .contract_address
+        .ok_or_else(|| eyre!("deployment receipt has no contract address"))
+}
+
+/// Deploy the FlashLoanTester pointing at `flash_loan_provider`.
+pub async fn deploy_tester(
+    client: &FlashLoanClient,
+    artifact: Artifact,
+    flash_loan_provider: Address,
+) -> Result<Address> {
+    let args = vec![Token::Address(flash_loan_provider)];
+    deploy_contract(client, artifact.abi, artifact.bytecode, args).await
+}
+
+/// Fail unless `args` match the constructor's inputs in number and type.
+pub fn check_constructor_args(abi: &Abi, args: &[Token]) -> Result<(), CapacitorError> {
+    let inputs: Vec<ParamType> = abi
+        .constructor
+        .as_ref()
+        .map(|constructor| {
+            constructor
+                .inputs
+                .iter()
+                .map(|input| input.kind.clone())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let matches = inputs.len() == args.len()
+        && inputs
+            .iter()
+            .zip(args)
+            .all(|(kind, arg)| arg.type_check(kind));
+    if !matches {
+        let types = |kinds: Vec<String>| format!("({})", kinds.join(","));
+        return Err(CapacitorError::ConstructorArgs {
+            expected: types(inputs.iter().map(ToString::to_string).collect()),
+            got: types(args.iter().map(describe_token).collect()),
+        });
+    }
+    Ok(())
+}
+
+// Token has no notion of its exact type (a uint is just a U256), so this is
+// a best-effort name for error messages.
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Address(_) => "address".into(),
+        Token::FixedBytes(bytes) => format!("bytes{}", bytes.len()),
+        Token::Bytes(_) => "bytes".into(),
+        Token::Int(_) => "int".into(),
+        Token::Uint(_) => "uint".into(),
+        Token::Bool(_) => "bool".into(),
+        Token::String(_) => "string".into(),
+        Token::FixedArray(items) | Token::Array(items) => format!(
+            "{}[]",
+            items.first().map(describe_token).unwrap_or_default()
+        ),
+        Token::Tuple(items) => format!(
+            "({})",
+            items
+                .iter()
+                .map(describe_token)
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+    }
+}
+
+/// Address a contract created by `deployer` in its transaction with `nonce`
+/// will have.
+pub fn predict_deploy_address(deployer: Address, nonce: U256) -> Address {
+    get_contract_address(deployer, nonce)
+}
+
+/// Address the owner's next deployment will get, from its pending nonce.
+///
+/// Another transaction from the owner sent before the deployment takes the
+/// nonce and moves the contract elsewhere; compare with
+/// [`check_deploy_address`] once deployed.
+pub async fn predict_next_deploy_address(client: &FlashLoanClient) -> Result<Address> {
+    let owner = client.owner_address();
+    let nonce = client
+        .provider
+        .get_transaction_count(owner, Some(BlockNumber::Pending.into()))
+        .await?;
+    Ok(predict_deploy_address(owner, nonce))
+}
+
+/// Fail if the contract did not land at the predicted address.
+pub fn check_deploy_address(predicted: Address, actual: Address) -> Result<(), CapacitorError> {
+    if predicted != actual {
+        return Err(CapacitorError::VerificationFailed {
+            what: "deployed address",
+            expected: predicted,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Check that a deployed tester points at `expected_provider` and is owned
+/// by `expected_owner`.
+///
+/// A wrong provider baked into the constructor only shows up later as
+/// confusing callback reverts, so it is caught right after deployment.
+pub async fn verify_deployment<M: Middleware + 'static>(
+    client: Arc<M>,
+    tester_addr: Address,
+    expected_provider: Address,
+    expected_owner: Address,
+) -> Result<()> {
+    let tester = IFlashLoanTester::new(tester_addr, client);
+
+    let provider = tester.flash_loan_provider().call().await?;
+    if provider != expected_provider {
+        return Err(CapacitorError::VerificationFailed {
+            what: "flashLoanProvider()",
+            expected: expected_provider,
+            actual: provider,
+        }
+        .into());
+    }
+
+    let owner = tester.owner().call().await?;
+    if owner != expected_owner {
+        return Err(CapacitorError::VerificationFailed {
+            what: "owner()",
+            expected: expected_owner,
+            actual: owner,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Whether the code at `contract_addr` is `artifact`'s runtime bytecode.
+///
+/// The trailing metadata hash is ignored, since it changes with comments
+/// and source paths, and so are the immutables the constructor filled in.
+/// A mismatch usually means the contract was compiled with different
+/// settings or from a different version of the source.
+pub async fn verify_bytecode<M: Middleware>(
+    client: &M,
+    contract_addr: Address,
+    artifact: &Artifact,
+) -> Result<bool>
+where
+    M::Error: 'static,
+{
+    let compiled = artifact
+        .deployed
+        .as_ref()
+        .ok_or_else(|| eyre!("artifact has no deployedBytecode"))?;
+    let code = client.get_code(contract_addr, None).await?;
+    if code.is_empty() {
+        return Err(CapacitorError::NoContractCode(contract_addr).into());
+    }
+    Ok(bytecode_matches(&code, compiled))
+}
+
+/// Compare runtime code with compiled bytecode, skipping the metadata hash
+/// and immutables.
+pub fn bytecode_matches(code: &[u8], compiled: &DeployedBytecode) -> bool {
+    let code = strip_metadata(code);
+    let expected = strip_metadata(&compiled.code);
+    code.len() == expected.len()
+        && code
+            .iter()
+            .zip(expected)
+            .enumerate()
+            .all(|(i, (a, b))| a == b || compiled.immutables.iter().any(|r| r.contains(&i)))
+}
+
+/// Whether runtime `code` dispatches the function with `selector`.
+///
+/// Solidity compiles every external function's selector into the
+/// dispatcher, so its absence means a call would hit the fallback and
+/// revert.
+pub fn bytecode_has_selector(code: &[u8], selector: Selector) -> bool {
+    code.windows(selector.len())
+        .any(|window| window == selector)
+}
+
+// solc appends CBOR-encoded metadata followed by its length as two bytes
+fn strip_metadata(code: &[u8]) -> &[u8] {
+    let Some(end) = code.len().checked_sub(2) else {
+        return code;
+    };
+    let len = usize::from(u16::from_be_bytes([code[end], code[end + 1]]));
+    match end.checked_sub(len) {
+        // CBOR maps start at 0xa0
+        Some(start) if len > 0 && (0xa0..=0xb7).contains(&code[start]) => &code[..start],
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixture artifacts for a contract `Sample`: runtime code is PUSH32 of
+    // an immutable, POP, STOP, then 12 bytes of metadata
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    const METADATA_LEN: usize = 12;
+
+    fn loader() -> ArtifactLoader {
+        ArtifactLoader::new(FIXTURES)
+    }
+
+    fn hardhat() -> Artifact {
+        load_artifact(format!(
+            "{}/artifacts/contracts/Sample.sol/Sample.json",
+            FIXTURES
+        ))
+        .unwrap()
+    }
+
+    fn foundry() -> Artifact {
+        load_artifact(format!("{}/out/Sample.sol/Sample.json", FIXTURES)).unwrap()
+    }
+
+    #[test]
+    fn both_shapes_normalize_to_the_same_artifact() {
+        let (hardhat, foundry) = (hardhat(), foundry());
+        assert_eq!(hardhat.abi, foundry.abi);
+        assert_eq!(hardhat.bytecode, foundry.bytecode);
+        assert_eq!(
+            hardhat.deployed.as_ref().unwrap().code,
+            foundry.deployed.as_ref().unwrap().code
+        );
+        assert_eq!(hardhat.abi.constructor.as_ref().unwrap().inputs.len(), 2);
+    }
+
+    #[test]
+    fn only_foundry_records_immutables() {
+        assert!(hardhat().deployed.unwrap().immutables.is_empty());
+        assert_eq!(foundry().deployed.unwrap().immutables, vec![1..33]);
+    }
+
+    #[test]
+    fn the_loader_tries_foundry_before_hardhat() {
+        let sample = loader().load("Sample").unwrap();
+        assert!(!sample.deployed.unwrap().immutables.is_empty());
+
+        let hardhat_only = loader().load("HardhatOnly").unwrap();
+        assert!(hardhat_only.abi.constructor.is_none());
+    }
+
+    #[test]
+    fn a_missing_artifact_lists_where_it_looked() {
+        let err = loader().load("Missing").unwrap_err().to_string();
+        assert!(err.contains("out/Missing.sol/Missing.json"), "{}", err);
+        assert!(
+            err.contains("artifacts/contracts/Missing.sol/Missing.json"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn an_artifact_without_bytecode_is_rejected() {
+        let err = loader().load("IPool").unwrap_err().to_string();
+        assert!(err.contains("has no bytecode"), "{}", err);
+    }
+
+    /// `compiled` as it would be deployed: immutable set, metadata replaced.
+    fn deployed_code(compiled: &DeployedBytecode) -> Vec<u8> {
+        let mut code = compiled.code.to_vec();
+        code[1..33].copy_from_slice(&[0xab; 32]);
+        // A different compiler version in the metadata
+        let metadata = code.len() - METADATA_LEN;
+        code[metadata + 7] = 0x09;
+        code
+    }
+
+    #[test]
+    fn metadata_is_ignored() {
+        let compiled = foundry().deployed.unwrap();
+        let mut code = compiled.code.to_vec();
+        let last_version_byte = code.len() - 3;
+        code[last_version_byte] ^= 0xff;
+        assert_ne!(code, compiled.code.to_vec());
+        assert!(bytecode_matches(&code, &compiled));
+    }
+
+    #[test]
+    fn filled_immutables_match_only_where_recorded() {
+        let foundry = foundry().deployed.unwrap();
+        let hardhat = hardhat().deployed.unwrap();
+        let code = deployed_code(&foundry);
+        assert!(bytecode_matches(&code, &foundry));
+        assert!(!bytecode_matches(&code, &hardhat));
+    }
+
+    #[test]
+    fn a_changed_instruction_does_not_match() {
+        let compiled = foundry().deployed.unwrap();
+        let mut code = compiled.code.to_vec();
+        code[33] = 0x00; // POP -> STOP
+        assert!(!bytecode_matches(&code, &compiled));
+
+        let mut longer = compiled.code.to_vec();
+        longer.insert(34, 0x00);
+        assert!(!bytecode_matches(&longer, &compiled));
+    }
+
+    #[test]
+    fn code_without_metadata_is_compared_whole() {
+        let compiled = DeployedBytecode {
+            code: vec![0x60, 0x00, 0x00].into(),
+            immutables: Vec::new(),
+        };
+        assert_eq!(strip_metadata(&compiled.code), &compiled.code[..]);
+        assert!(bytecode_matches(&[0x60, 0x00, 0x00], &compiled));
+        assert!(!bytecode_matches(&[0x60, 0x01, 0x00], &compiled));
+        assert_eq!(strip_metadata(&[0x00]), &[0x00]);
+    }
+
+    #[test]
+    fn constructor_args_must_match_in_number_and_type() {
+        let abi = hardhat().abi;
+        let provider = Token::Address(Address::repeat_byte(1));
+        let supply = Token::Uint(1_000.into());
+        assert!(check_constructor_args(&abi, &[provider.clone(), supply.clone()]).is_ok());
+
+        let err = check_constructor_args(&abi, &[provider]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Constructor expects (address,uint256), got (address)"
+        );
+        assert_eq!(err.exit_code(), 2);
+
+        let err = check_constructor_args(&abi, &[supply.clone(), supply]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Constructor expects (address,uint256), got (uint,uint)"
+        );
+    }
+
+    #[test]
+    fn no_constructor_takes_no_args() {
+        let abi = loader().load("HardhatOnly").unwrap().abi;
+        assert!(check_constructor_args(&abi, &[]).is_ok());
+        assert!(check_constructor_args(&abi, &[Token::Bool(true)]).is_err());
+    }
+
+    #[test]
+    fn selectors_are_found_anywhere_in_the_code() {
+        let selector = [0xde, 0xad, 0xbe, 0xef];
+        // PUSH4 selector, EQ, as in a dispatcher
+        assert!(bytecode_has_selector(
+            &[0x63, 0xde, 0xad, 0xbe, 0xef, 0x14],
+            selector
+        ));
+        assert!(bytecode_has_selector(&selector, selector));
+        assert!(!bytecode_has_selector(
+            &[0x63, 0xde, 0xad, 0xbe, 0x14],
+            selector
+        ));
+        assert!(!bytecode_has_selector(&[], selector));
+
+        let code = hardhat().deployed.unwrap().code;
+        assert!(!bytecode_has_selector(&code, selector));
+    }
+}
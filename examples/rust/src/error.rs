@@ -0,0 +1,245 @@
+//! Errors raised by the flash loan flows.
+//!
+//! The binaries exit with a code chosen by the kind of failure, so scripts
+//! can tell a bad config from a reverted loan without parsing output:
+//!
+//! | Code | Meaning |
+//! |------|---------|
+//! | 0 | Success |
+//! | 1 | Any other error (RPC, ABI decoding, ...) |
+//! | 2 | Configuration error |
+//! | 3 | Preflight check failed; nothing was sent |
+//! | 4 | A transaction reverted or its result failed verification |
+//! | 5 | A transaction was not mined in time |
+//! | 6 | The RPC serves a different chain than `network` |
+
+use crate::network::Network;
+use ethers::types::{Address, H256, U256};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CapacitorError {
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("RPC serves chain ID {actual}, but {network} is chain ID {}", network.chain_id())]
+    NetworkMismatch { network: Network, actual: u64 },
+
+    #[error("Transaction {0:?} reverted")]
+    Reverted(H256),
+
+    #[error("Fee exceeded: expected {expected}, allowed up to {max}, charged {charged}")]
+    FeeExceeded {
+        expected: U256,
+        max: U256,
+        charged: U256,
+    },
+
+    #[error("{what} is {actual:?}, expected {expected:?}")]
+    WiringMismatch {
+        what: &'static str,
+        expected: Address,
+        actual: Address,
+    },
+
+    #[error("{what} is {actual:?} after the transaction, expected {expected:?}")]
+    VerificationFailed {
+        what: &'static str,
+        expected: Address,
+        actual: Address,
+    },
+
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    #[error("No contract code at {0:?}")]
+    NoContractCode(Address),
+
+    #[error("Unknown token {symbol} on {network} (known: {known})")]
+    UnknownToken {
+        symbol: String,
+        network: Network,
+        known: String,
+    },
+
+    #[error("Pool has {available} available, requested {requested}")]
+    PoolInsufficientLiquidity { requested: U256, available: U256 },
+
+    #[error("Loan of {requested} exceeds max_loan_amount ({cap})")]
+    LoanCapExceeded { requested: U256, cap: U256 },
+
+    #[error("Provider has no pool for token {token:?} (supported: {supported})")]
+    NoPool { token: Address, supported: String },
+
+    #[error("Constructor expects {expected}, got {got}")]
+    ConstructorArgs { expected: String, got: String },
+
+    #[error("Repayment mismatch: reserve went from {before} to {after}, expected +{expected_fee}")]
+    RepaymentMismatch {
+        expected_fee: U256,
+        before: U256,
+        after: U256,
+    },
+
+    #[error("Contract holds {have} of the token, needs {need} to pay the fee")]
+    Underfunded { have: U256, need: U256 },
+
+    #[error("{holder:?} holds none of token {token:?}")]
+    NoTokenBalance { holder: Address, token: Address },
+
+    #[error("Wallet holds {have} wei of the native token, needs {need} to pay for gas")]
+    InsufficientGas { have: U256, need: U256 },
+
+    #[error("Gas limit {limit} is above the block gas limit {block_gas_limit}")]
+    GasLimitTooHigh { limit: U256, block_gas_limit: U256 },
+
+    #[error("{failed} of {total} self-test modes behaved unexpectedly")]
+    SelfTestFailed { failed: usize, total: usize },
+
+    #[error("Transaction {tx_hash:?} was not mined within {waited:?}")]
+    Timeout { tx_hash: H256, waited: Duration },
+
+    #[error("{holder:?} holds {actual} of token {token:?}, expected {expected}")]
+    BalanceExpectation {
+        holder: Address,
+        token: Address,
+        actual: U256,
+        expected: String,
+    },
+
+    #[error("Cannot combine amounts with {left} and {right} decimals")]
+    DecimalsMismatch { left: u8, right: u8 },
+
+    #[error("Cannot subtract {right} from {left}")]
+    AmountUnderflow { left: U256, right: U256 },
+
+    #[error("Cannot add {right} to {left} without overflowing")]
+    AmountOverflow { left: U256, right: U256 },
+
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    #[error("{tokens} token(s) but {amounts} amount(s)")]
+    LengthMismatch { tokens: usize, amounts: usize },
+}
+
+impl CapacitorError {
+    /// Process exit code for this error; see the module docs.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CapacitorError::Config(_)
+            | CapacitorError::InvalidAddress(_)
+            | CapacitorError::UnknownToken { .. }
+            | CapacitorError::ConstructorArgs { .. }
+            | CapacitorError::DecimalsMismatch { .. }
+            | CapacitorError::AmountUnderflow { .. }
+            | CapacitorError::AmountOverflow { .. }
+            | CapacitorError::GasLimitTooHigh { .. }
+            | CapacitorError::LengthMismatch { .. } => 2,
+            CapacitorError::NoContractCode(_)
+            | CapacitorError::WiringMismatch { .. }
+            | CapacitorError::PoolInsufficientLiquidity { .. }
+            | CapacitorError::LoanCapExceeded { .. }
+            | CapacitorError::NoPool { .. }
+            | CapacitorError::Underfunded { .. }
+            | CapacitorError::NoTokenBalance { .. }
+            | CapacitorError::InsufficientGas { .. }
+            | CapacitorError::Unsupported(_) => 3,
+            CapacitorError::Reverted(_)
+            | CapacitorError::FeeExceeded { .. }
+            | CapacitorError::RepaymentMismatch { .. }
+            | CapacitorError::BalanceExpectation { .. }
+            | CapacitorError::VerificationFailed { .. }
+            | CapacitorError::SelfTestFailed { .. } => 4,
+            CapacitorError::Timeout { .. } => 5,
+            CapacitorError::NetworkMismatch { .. } => 6,
+        }
+    }
+}
+
+/// Exit code for a top-level error: that of the first `CapacitorError` in
+/// its chain, or 1 when there is none.
+pub fn exit_code(report: &eyre::Report) -> i32 {
+    report
+        .chain()
+        .find_map(|e| e.downcast_ref::<CapacitorError>())
+        .map_or(1, CapacitorError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::WrapErr;
+
+    #[test]
+    fn each_kind_of_failure_has_its_exit_code() {
+        let cases = [
+            (CapacitorError::Config("owner key is not valid".into()), 2),
+            (
+                CapacitorError::NoTokenBalance {
+                    holder: Address::zero(),
+                    token: Address::zero(),
+                },
+                3,
+            ),
+            (
+                CapacitorError::LoanCapExceeded {
+                    requested: U256::from(2),
+                    cap: U256::one(),
+                },
+                3,
+            ),
+            (
+                CapacitorError::WiringMismatch {
+                    what: "contract owner",
+                    expected: Address::zero(),
+                    actual: Address::repeat_byte(1),
+                },
+                3,
+            ),
+            (CapacitorError::Reverted(H256::zero()), 4),
+            (
+                CapacitorError::VerificationFailed {
+                    what: "contract owner",
+                    expected: Address::zero(),
+                    actual: Address::repeat_byte(1),
+                },
+                4,
+            ),
+            (
+                CapacitorError::SelfTestFailed {
+                    failed: 1,
+                    total: 3,
+                },
+                4,
+            ),
+            (
+                CapacitorError::Timeout {
+                    tx_hash: H256::zero(),
+                    waited: Duration::from_secs(1),
+                },
+                5,
+            ),
+            (
+                CapacitorError::NetworkMismatch {
+                    network: Network::PlasmaTestnet,
+                    actual: 1,
+                },
+                6,
+            ),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.exit_code(), code, "{}", error);
+        }
+    }
+
+    #[test]
+    fn reports_exit_with_the_first_capacitor_error_in_their_chain() {
+        let wrapped = Err::<(), _>(CapacitorError::Reverted(H256::zero()))
+            .wrap_err("flash loan failed")
+            .unwrap_err();
+        assert_eq!(exit_code(&wrapped), 4);
+        assert_eq!(exit_code(&eyre::eyre!("connection refused")), 1);
+    }
+}
@@ -0,0 +1,88 @@
+//! Decoding of flash loan events.
+//!
+//! The provider emits `FlashLoan` for every loan it serves. The
+//! FlashLoanTester emits `FlashLoanReceived` from inside its callback; that
+//! event carries no initiator, so the emitting contract (the receiver) is
+//! reported in its place.
+
+use ethers::{
+    contract::{abigen, EthEvent},
+    types::{Address, Log, H256, U256},
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+abigen!(
+    IFlashLoanEvents,
+    r#"[
+        event FlashLoan(address indexed initiator, address indexed token, uint256 amount, uint256 fee)
+        event FlashLoanReceived(address token, uint256 amount, uint256 fee)
+    ]"#,
+);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlashLoanExecuted {
+    pub initiator: Address,
+    pub token: Address,
+    pub amount: U256,
+    pub fee: U256,
+    pub block_number: Option<u64>,
+    pub tx_hash: Option<H256>,
+    /// Set when the log was dropped by a chain reorganisation.
+    pub removed: bool,
+}
+
+/// Topics of every event `decode_log` understands.
+pub fn flash_loan_topics() -> Vec<H256> {
+    vec![
+        FlashLoanFilter::signature(),
+        FlashLoanReceivedFilter::signature(),
+    ]
+}
+
+/// Decode a provider `FlashLoan` or tester `FlashLoanReceived` log.
+pub fn decode_log(log: &Log) -> Option<FlashLoanExecuted> {
+    let raw = log.clone().into();
+    let (initiator, token, amount, fee) = match log.topics.first() {
+        Some(topic) if *topic == FlashLoanFilter::signature() => {
+            let event = FlashLoanFilter::decode_log(&raw).ok()?;
+            (event.initiator, event.token, event.amount, event.fee)
+        }
+        Some(topic) if *topic == FlashLoanReceivedFilter::signature() => {
+            let event = FlashLoanReceivedFilter::decode_log(&raw).ok()?;
+            (log.address, event.token, event.amount, event.fee)
+        }
+        _ => return None,
+    };
+
+    Some(FlashLoanExecuted {
+        initiator,
+        token,
+        amount,
+        fee,
+        block_number: log.block_number.map(|block| block.as_u64()),
+        tx_hash: log.transaction_hash,
+        removed: log.removed.unwrap_or(false),
+    })
+}
+
+/// Fee paid per token in `logs`, counting each loan once.
+///
+/// A loan through the FlashLoanTester logs both the provider's `FlashLoan`
+/// and the tester's `FlashLoanReceived`, so the tester's events only count
+/// when the provider logged none.
+pub fn fees_paid(logs: &[Log]) -> BTreeMap<Address, U256> {
+    let is_provider = |log: &Log| log.topics.first() == Some(&FlashLoanFilter::signature());
+    let provider_logged = logs.iter().any(is_provider);
+
+    let mut fees = BTreeMap::new();
+    for log in logs {
+        if provider_logged && !is_provider(log) {
+            continue;
+        }
+        if let Some(event) = decode_log(log) {
+            *fees.entry(event.token).or_insert_with(U256::zero) += event.fee;
+        }
+    }
+    fees
+}
@@ -1,99 +1,188 @@
 // Step 2: Execute Flash Loan
 //
 // This program executes a flash loan using your deployed contract.
-// Replace <DEPLOYED_CONTRACT_ADDRESS> with the address from step 1.
+// Set `deployed_contract` in capacitor.toml (or DEPLOYED_CONTRACT) to the
+// address from step 1.
 
 use ethers::{
-    prelude::*,
-    utils::{format_ether, format_units, parse_ether},
+    providers::Middleware,
+    types::{Address, U256},
+    utils::format_units,
 };
-use eyre::Result;
-use std::sync::Arc;
-
-// Configuration
-const PLASMA_RPC: &str = "https://testnet-rpc.plasma.to";
-const FLASH_LOAN_PROVIDER: &str = "0x63A6E3A5743F75388e58e8B778023380694aD3e5";
-const TUSDT_TOKEN: &str = "0xE5aE1FF9c761F581ac4F1d3075e12ae340500C99";
-const PRIVATE_KEY: &str = "<YOUR_PRIVATE_KEY_HERE>";
-
-// YOUR DEPLOYED CONTRACT ADDRESS (from step 1)
-const DEPLOYED_CONTRACT: &str = "<DEPLOYED_CONTRACT_ADDRESS>";
-
-// ERC20 ABI (simplified)
-abigen!(
-    IERC20,
-    r#"[
-        function balanceOf(address account) external view returns (uint256)
-        function transfer(address to, uint256 amount) external returns (bool)
-        function symbol() external view returns (string)
-        function decimals() external view returns (uint8)
-    ]"#,
-);
-
-// FlashLoanTester ABI
-abigen!(
-    IFlashLoanTester,
-    r#"[
-        function owner() external view returns (address)
-        function testFlashLoan(address token, uint256 amount, uint8 mode) external
-    ]"#,
-);
+use clap::Parser;
+use eyre::{eyre, Result};
+use std::{path::PathBuf, process};
+use flashloan_example::{
+    abi::{IERC20, IFlashLoanTester},
+    access_list::{generate_access_list, AccessListCheck},
+    confirm::{confirm_send, Summary},
+    cost::{cost_report, CostReport},
+    error::exit_code,
+    amount::TokenAmount,
+    fee::check_fee_paid,
+    fee_resolver::FeeResolver,
+    flash_loan::{execute_flash_loan, FlashLoanMode, FlashLoanOutcome, FlashLoanParams},
+    poll::{await_with_diagnostics, TxStatus},
+    persist::persist_run,
+    pool::{assert_repayment, pool_liquidity},
+    preflight::{check_funded, check_gas_funds, check_loan_cap, check_provider, preflight},
+    send::TxBuilder,
+    tokens::{token_decimals, TokenBook},
+    CapacitorError, Config, FlashLoanClient,
+};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(about = "Execute a flash loan through your deployed contract")]
+struct Args {
+    /// Token to borrow: a known symbol (e.g. TUSDT) or a 0x address
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Amount to borrow in whole tokens, e.g. 100 or 2.5
+    #[arg(long, default_value = "100")]
+    amount: String,
+
+    /// Amount to borrow in base units, used verbatim (no decimal conversion)
+    #[arg(long, value_name = "U256", value_parser = parse_base_units, conflicts_with = "amount")]
+    amount_raw: Option<U256>,
+
+    /// Flash loan provider to check against instead of the configured one
+    #[arg(long, value_name = "ADDRESS")]
+    provider: Option<Address>,
+
+    /// Gas limit to send with if estimation reverts, instead of aborting
+    #[arg(long, value_name = "LIMIT")]
+    force_gas: Option<u64>,
+
+    /// Send the loan with a generated access list when it lowers the gas estimate
+    #[arg(long)]
+    access_list: bool,
+
+    /// Skip funding the contract; its callback must generate the fee itself
+    #[arg(long)]
+    no_prefund: bool,
+
+    /// Check that the provider's reserve grew by exactly the fee
+    #[arg(long)]
+    verify_repayment: bool,
+
+    /// Write the receipt, events and outcome to a JSON file in output_dir
+    #[arg(long)]
+    save: bool,
+
+    /// Skip the mainnet confirmation prompt
+    #[arg(long)]
+    yes: bool,
+
+    /// Record every JSON-RPC request and response to this file
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Replay a recorded session instead of talking to the network
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+
+    /// Serve Prometheus metrics on this address, e.g. 127.0.0.1:9100
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<std::net::SocketAddr>,
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        process::exit(exit_code(&e));
+    }
+}
+
+async fn run() -> Result<()> {
+    let args = Args::parse();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+
     println!("\n⚡ Execute Flash Loan\n");
     println!("{}", "=".repeat(60));
 
+    let mut config = Config::load().map_err(|e| CapacitorError::Config(format!("{:#}", e)))?;
+    config.rpc.override_session(args.record.clone(), args.replay.clone());
+    if let Some(provider) = args.provider {
+        config.flash_loan_provider = provider;
+    }
+    if let Some(limit) = args.force_gas {
+        config.fallback_gas_limit = Some(limit);
+    }
+
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = args.metrics_addr {
+        flashloan_example::metrics::serve(addr).await?;
+        println!("📈 Metrics: http://{}/metrics", addr);
+    }
+    let tusdt_address = match args.token.as_deref() {
+        Some(token) => TokenBook::from_config(&config).resolve(config.network, token)?,
+        None => config.token,
+    };
+
     // Validate contract address
-    if DEPLOYED_CONTRACT == "<DEPLOYED_CONTRACT_ADDRESS>" || !DEPLOYED_CONTRACT.starts_with("0x") {
+    let Some(contract_address) = config.deployed_contract else {
         println!("\n❌ Error: Invalid contract address!");
-        println!("\nPlease update DEPLOYED_CONTRACT in src/execute.rs:");
-        println!("   const DEPLOYED_CONTRACT: &str = \"0x...your address...\";");
+        println!("\nPlease set the deployed contract in capacitor.toml:");
+        println!("   deployed_contract = \"0x...your address...\"");
+        println!("   OR export DEPLOYED_CONTRACT=0x...your address...");
         println!("\nRun deployment first:");
         println!("   cargo run --bin deploy");
         println!("   OR use Node.js/Python deployment scripts\n");
-        return Ok(());
+        return Err(CapacitorError::Config("deployed_contract is not set".into()).into());
+    };
+
+    // Validate private keys
+    if config.owner_key().is_none() || config.funder_key().is_none() {
+        println!("\n❌ Error: Invalid private key!");
+        println!("\nPlease set owner_key (and optionally funder_key) in capacitor.toml");
+        println!("   OR export OWNER_KEY=0x...your key... (FUNDER_KEY=0x... for a separate funder)");
+        return Err(CapacitorError::Config("owner or funder key is not valid".into()).into());
     }
 
-    // Setup provider
-    let provider = Provider::<Http>::try_from(PLASMA_RPC)?;
-    let chain_id = provider.get_chainid().await?;
-
-    // Setup wallet
-    let wallet: LocalWallet = PRIVATE_KEY.parse()?;
-    let wallet = wallet.with_chain_id(chain_id.as_u64());
-    let address = wallet.address();
+    // Setup provider and wallets
+    let client = FlashLoanClient::connect(&config).await?;
+    let address = client.owner_address();
+    let funder_address = client.funder_address();
 
-    println!("\n📍 Network: Plasma Testnet");
+    println!("\n📍 Network: {}", config.network);
     println!("👤 Wallet: {:?}", address);
-    println!("📄 Contract: {}", DEPLOYED_CONTRACT);
-
-    // Create client
-    let client = Arc::new(SignerMiddleware::new(provider.clone(), wallet.clone()));
+    if client.has_separate_funder() {
+        println!("💳 Funder: {:?}", funder_address);
+    }
+    println!("📄 Contract: {:?}", contract_address);
+    println!("🧾 Transaction Type: {}", client.tx_type);
+    if let Some(relay) = &client.relay {
+        println!("🔒 Submission: private relay {}", relay.url());
+    }
 
-    // Get TUSDT contract
-    let tusdt_address: Address = TUSDT_TOKEN.parse()?;
-    let tusdt = IERC20::new(tusdt_address, client.clone());
+    // Get TUSDT contract (fee tokens are sent from the funder)
+    let tusdt = IERC20::new(tusdt_address, client.funder.clone());
 
-    // Check wallet balance
-    let wallet_balance = tusdt.balance_of(address).await?;
+    // Check funder balance
     let symbol = tusdt.symbol().await?;
-    let decimals = tusdt.decimals().await?;
+    let decimals =
+        token_decimals(client.funder.clone(), tusdt_address, &config.decimals_override).await?;
+    let amount = |raw: U256| TokenAmount::from_raw(raw, decimals);
+    let wallet_balance = amount(tusdt.balance_of(funder_address).await?);
 
-    println!("💰 Wallet Balance: {} {}",
-        format_units(wallet_balance, decimals as u32)?,
-        symbol
-    );
+    println!("💰 Wallet Balance: {} {}", wallet_balance, symbol);
 
-    if wallet_balance.is_zero() {
+    if wallet_balance.is_zero() && !args.no_prefund {
         println!("\n❌ Error: No TUSDT balance!");
         println!("   Get tokens from: https://gas.zip/faucet/plasma");
-        return Ok(());
+        return Err(CapacitorError::NoTokenBalance { holder: funder_address, token: tusdt_address }.into());
     }
 
-    // Get deployed contract
-    let contract_address: Address = DEPLOYED_CONTRACT.parse()?;
-    let tester = IFlashLoanTester::new(contract_address, client.clone());
+    // Get deployed contract (the owner initiates the flash loan)
+    let tester = IFlashLoanTester::new(contract_address, client.owner.clone());
 
     // Verify ownership
     let owner = tester.owner().await?;
@@ -101,100 +190,228 @@ async fn main() -> Result<()> {
         println!("\n❌ Error: You are not the owner of this contract!");
         println!("   Contract owner: {:?}", owner);
         println!("   Your address: {:?}", address);
-        return Ok(());
+        return Err(CapacitorError::WiringMismatch {
+            what: "contract owner",
+            expected: address,
+            actual: owner,
+        }
+        .into());
     }
 
-    println!("\n{}", "=".repeat(60));
-    println!("STEP 1: Fund Contract with Fee Amount");
-    println!("{}", "=".repeat(60));
+    // The tester borrows from the provider it was deployed with
+    match check_provider(&client, contract_address, config.flash_loan_provider).await? {
+        Some(wired) => {
+            println!("⚠️  Provider mismatch: checking {:?}, but the contract borrows from {:?}",
+                config.flash_loan_provider,
+                wired
+            );
+            println!("   Liquidity and repayment checks will not describe the pool actually used");
+        }
+        None => println!("🏦 Provider: {:?}", config.flash_loan_provider),
+    }
 
     // Flash loan parameters
-    let loan_amount = parse_ether(100)?; // 100 TUSDT
-    let fee = loan_amount / U256::from(10000); // 0.01%
-    let funding_amount = parse_ether(1)?; // 1 TUSDT
+    let loan_amount = match args.amount_raw {
+        Some(raw) => amount(raw),
+        None => TokenAmount::from_human(&args.amount, decimals)?,
+    };
+    if let Err(e) = check_loan_cap(loan_amount.raw(), config.max_loan_amount(decimals)?) {
+        if let CapacitorError::LoanCapExceeded { cap, .. } = e {
+            println!("\n❌ Preflight failed: loan of {} {} exceeds max_loan_amount {} {}",
+                loan_amount,
+                symbol,
+                loan_amount.with_raw(cap),
+                symbol
+            );
+        }
+        return Err(e.into());
+    }
+    // Rate read from the provider; the examples' 0.01% if it doesn't say
+    let fees = FeeResolver::new(client.owner.clone(), tusdt_address);
+    let fee_info = fees
+        .fee_info_or_fallback(config.flash_loan_provider, tusdt_address, loan_amount.raw())
+        .await?;
+    let fee = loan_amount.with_raw(fee_info.absolute);
+    let funding_amount = TokenAmount::from_human("1", decimals)?; // 1 token
+
+    let mut params = FlashLoanParams {
+        tester: contract_address,
+        token: tusdt_address,
+        amount: loan_amount,
+        expected_fee: fee,
+        mode: FlashLoanMode::Success.into(),
+        access_list: None,
+    };
+
+    // Check the pool can serve the loan before spending gas
+    match preflight(&client, config.flash_loan_provider, &params).await {
+        Ok(checks) => {
+            println!("🏦 Pool Liquidity: {} {}", amount(checks.liquidity), symbol);
+        }
+        Err(e) => {
+            println!("\n❌ Preflight failed: {}", e);
+            if let Some(CapacitorError::NoPool { .. }) = e.downcast_ref() {
+                println!("   Borrow a token the provider lends with --token");
+                println!("   List them with: cargo run --bin capacitor -- tokens");
+            }
+            return Err(e);
+        }
+    }
 
-    println!("\n💸 Sending {} {} to contract for fees...",
-        format_units(funding_amount, decimals as u32)?,
-        symbol
+    let transfer = TxBuilder::call(
+        &client,
+        &client.funder,
+        tusdt.transfer(contract_address, funding_amount.raw()),
     );
 
-    // Transfer tokens to contract
-    let transfer_tx = tusdt.transfer(contract_address, funding_amount);
-    let pending_tx = transfer_tx.send().await?;
-    println!("⏳ Waiting for transfer confirmation...");
-    let _receipt = pending_tx.await?;
+    let gas_price = client.provider.get_gas_price().await?;
+    // The loan can only be estimated once the contract holds the fee
+    let gas_cost = if args.no_prefund {
+        println!("⚠️  No pre-funding: the callback must generate the {} {} fee itself", fee, symbol);
+        println!("   Otherwise repayment fails and the loan reverts");
+        "loan estimated when it is sent".to_string()
+    } else {
+        // The funder pays gas for the transfer before anything else is sent
+        let (transfer_gas, transfer_cost) = transfer.estimate_limit_and_cost().await?;
+        if let Err(e) = check_gas_funds(&client, funder_address, transfer_cost).await {
+            println!("\n❌ Preflight failed: {}", e);
+            println!("   Get XPL from: https://gas.zip/faucet/plasma");
+            return Err(e);
+        }
+        let funding = CostReport::estimated(transfer_gas, gas_price, config.xpl_price_usd);
+        format!("{} for funding, plus the loan (estimated once funded)", funding)
+    };
+
+    let (action, funding) = if args.no_prefund {
+        ("Execute flash loan without pre-funding", "none (callback repays the fee)".to_string())
+    } else {
+        ("Fund contract and execute flash loan", format!("{} {}", funding_amount, symbol))
+    };
+    let summary = Summary::new(config.network, action)
+        .with("Token", format!("{} ({:?})", symbol, tusdt_address))
+        .with("Amount", format!("{} {}", loan_amount, symbol))
+        .with("Estimated Fee", format!("{} {} ({} bps)", fee, symbol, fee_info.bps))
+        .with("Funding", funding)
+        .with("Gas Price", format!("{} gwei", format_units(gas_price, "gwei")?))
+        .with("Gas Cost", gas_cost);
+    if !confirm_send(&summary, args.yes)? {
+        println!("\n❌ Aborted; nothing was sent.\n");
+        return Ok(());
+    }
+
+    let mut step = 0;
+
+    if !args.no_prefund {
+        step += 1;
+        println!("\n{}", "=".repeat(60));
+        println!("STEP {}: Fund Contract with Fee Amount", step);
+        println!("{}", "=".repeat(60));
+
+        println!("\n💸 Sending {} {} to contract for fees...", funding_amount, symbol);
+
+        // Transfer tokens to contract
+        let pending_tx = transfer.send().await?;
+        let transfer_hash = pending_tx.tx_hash();
+        println!("⏳ Waiting for transfer confirmation...");
+        match await_with_diagnostics(&client.provider, transfer_hash, client.poll).await? {
+            TxStatus::Mined(_) => {}
+            TxStatus::Pending => {
+                println!("\n❌ Funding transfer {:?} is still pending in the mempool", transfer_hash);
+                println!("   Wait for it, or replace it: cargo run --bin capacitor -- cancel {:?}", transfer_hash);
+                return Err(CapacitorError::Timeout {
+                    tx_hash: transfer_hash,
+                    waited: client.poll.max_duration,
+                }
+                .into());
+            }
+            TxStatus::Unconfirmed(_) => {
+                println!("\n❌ Funding transfer {:?} was mined but is not yet confirmed", transfer_hash);
+                println!("   Check it before sending again: cargo run --bin capacitor -- inspect {:?}", transfer_hash);
+                return Err(CapacitorError::Timeout {
+                    tx_hash: transfer_hash,
+                    waited: client.poll.max_duration,
+                }
+                .into());
+            }
+            status => {
+                println!("\n❌ Funding transfer {:?}: {}", transfer_hash, status);
+                println!("   Nothing was funded; run again to resend");
+                return Err(eyre!("funding transfer {:?} {}", transfer_hash, status));
+            }
+        }
+
+        // Fail here rather than on repayment if the transfer delivered too little
+        let contract_balance = match check_funded(&client, &params).await {
+            Ok(balance) => amount(balance),
+            Err(e) => {
+                println!("\n❌ Funding check failed: {}", e);
+                return Err(e);
+            }
+        };
+        println!("✅ Contract Balance: {} {}", contract_balance, symbol);
+    }
 
-    let contract_balance = tusdt.balance_of(contract_address).await?;
-    println!("✅ Contract Balance: {} {}",
-        format_units(contract_balance, decimals as u32)?,
-        symbol
+    // The loan only estimates cleanly once the contract can pay the fee.
+    // Without pre-funding, a callback that cannot repay fails right here.
+    let mut loan = TxBuilder::call(
+        &client,
+        &client.owner,
+        tester.test_flash_loan(tusdt_address, params.amount.raw(), params.mode),
     );
+    if args.access_list {
+        match generate_access_list(&*client.owner, &loan.tx()).await {
+            AccessListCheck::Saves { list, without, with } => {
+                let saved = without - with;
+                println!("🗂️  Access list: saves {} gas ({} -> {})", saved, without, with);
+                loan = loan.access_list(list.clone());
+                params.access_list = Some(list);
+            }
+            AccessListCheck::NoSavings { without, with } => {
+                println!("🗂️  Access list: not used ({} gas with it, {} without)", with, without);
+            }
+            AccessListCheck::Unavailable(reason) => {
+                println!("🗂️  Access list: not available ({}), sending without", reason);
+            }
+        }
+    }
+    let loan_cost = match loan.estimate_cost().await {
+        Ok(cost) => cost,
+        Err(e) => {
+            println!("\n❌ Flash loan would revert: {}", e);
+            if args.no_prefund {
+                println!("   Without pre-funding the callback must cover the fee itself");
+            }
+            return Err(e);
+        }
+    };
+    if let Err(e) = check_gas_funds(&client, address, loan_cost).await {
+        println!("\n❌ Preflight failed: {}", e);
+        println!("   Get XPL from: https://gas.zip/faucet/plasma");
+        return Err(e);
+    }
 
+    step += 1;
     println!("\n{}", "=".repeat(60));
-    println!("STEP 2: Execute Flash Loan");
+    println!("STEP {}: Execute Flash Loan", step);
     println!("{}", "=".repeat(60));
 
     println!("\n📋 Flash Loan Parameters:");
     println!("   Token: {}", symbol);
-    println!("   Amount: {} {}", format_units(loan_amount, decimals as u32)?, symbol);
-    println!("   Fee: {} {} (0.01%)", format_units(fee, decimals as u32)?, symbol);
-    println!("   Total Repayment: {} {}",
-        format_units(loan_amount + fee, decimals as u32)?,
-        symbol
-    );
+    println!("   Amount: {} {}", loan_amount, symbol);
+    println!("   Fee: {} {} ({} bps)", fee, symbol, fee_info.bps);
+    println!("   Total Repayment: {} {}", loan_amount.try_add(fee)?, symbol);
 
-    println!("\n⏳ Executing flash loan transaction...");
-
-    // Execute flash loan
-    // Mode 0 = SUCCESS
-    let flashloan_tx = tester.test_flash_loan(tusdt_address, loan_amount, 0);
-
-    let pending = flashloan_tx.send().await?;
-    let tx_hash = pending.tx_hash();
+    let reserve_before = if args.verify_repayment {
+        let provider = config.flash_loan_provider;
+        Some(pool_liquidity(client.owner.clone(), provider, tusdt_address).await?)
+    } else {
+        None
+    };
 
-    println!("📝 Transaction sent: {:?}", tx_hash);
-    println!("   View: https://testnet.plasmascan.to/tx/{:?}", tx_hash);
-    println!("⏳ Waiting for confirmation...");
-
-    match pending.await {
-        Ok(Some(receipt)) => {
-            println!("\n{}", "=".repeat(60));
-            println!("✅ FLASH LOAN EXECUTED SUCCESSFULLY!");
-            println!("{}", "=".repeat(60));
-
-            println!("\n📊 Transaction Results:");
-            println!("   Block: {:?}", receipt.block_number);
-            println!("   Gas Used: {}", receipt.gas_used.unwrap_or_default());
-            println!("   Status: {}",
-                if receipt.status.unwrap_or_default() == U64::from(1) {
-                    "✅ Success"
-                } else {
-                    "❌ Failed"
-                }
-            );
-
-            // Check balance after
-            let final_balance = tusdt.balance_of(contract_address).await?;
-            println!("\n💰 Contract Balance After: {} {}",
-                format_units(final_balance, decimals as u32)?,
-                symbol
-            );
-
-            // Calculate fee paid
-            let fee_paid = contract_balance - final_balance;
-            println!("📉 Fee Paid: {} {}",
-                format_units(fee_paid, decimals as u32)?,
-                symbol
-            );
+    println!("\n⏳ Executing flash loan transaction...");
 
-            println!("\n✅ Verification:");
-            println!("   Expected Fee: {} {}", format_units(fee, decimals as u32)?, symbol);
-            println!("   Actual Fee: {} {}", format_units(fee_paid, decimals as u32)?, symbol);
-            println!("   Match: {}", if fee_paid == fee { "✅ Yes" } else { "❌ No" });
-        }
-        Ok(None) => {
-            println!("\n❌ Transaction receipt not found");
-        }
+    let outcome = match execute_flash_loan(&client, &params).await {
+        Ok(outcome) => outcome,
         Err(e) => {
             println!("\n{}", "=".repeat(60));
             println!("❌ FLASH LOAN FAILED");
@@ -209,10 +426,71 @@ async fn main() -> Result<()> {
             println!("   • Gas too low: Try increasing gas limit");
             println!("\n   See ERRORS.md for detailed troubleshooting");
 
-            return Err(e.into());
+            return Err(e);
         }
+    };
+
+    println!("📝 Transaction: {:?}", outcome.tx_hash);
+    println!("   View: {}", config.network.tx_url(outcome.tx_hash));
+
+    let cost = cost_report(&outcome.receipt, config.xpl_price_usd);
+
+    if !outcome.success {
+        println!("\n{}", "=".repeat(60));
+        println!("❌ FLASH LOAN REVERTED");
+        println!("{}", "=".repeat(60));
+
+        println!("\n📊 Transaction Results:");
+        println!("   Block: {:?}", outcome.block_number);
+        println!("   Gas Used: {}", outcome.gas_used);
+        println!("   Transaction cost: {}", cost);
+        println!("   Status: ❌ Reverted; no tokens were borrowed");
+        println!("\n   See ERRORS.md for detailed troubleshooting");
+
+        let error = CapacitorError::Reverted(outcome.tx_hash);
+        save_run(&args, &config, &outcome);
+        return Err(error.into());
+    }
+
+    println!("\n{}", "=".repeat(60));
+    println!("✅ FLASH LOAN EXECUTED SUCCESSFULLY!");
+    println!("{}", "=".repeat(60));
+
+    println!("\n📊 Transaction Results:");
+    println!("   Block: {:?}", outcome.block_number);
+    println!("   Gas Used: {}", outcome.gas_used);
+    println!("   Transaction cost: {}", cost);
+    println!("   Status: ✅ Success");
+
+    // A single-token loan has exactly one leg
+    let leg = &outcome.legs[0];
+    println!("\n💰 Contract Balance After: {} {}", leg.balance_after, symbol);
+    println!("📉 Fee Paid: {} {}", leg.actual_fee, symbol);
+
+    println!("\n✅ Verification:");
+    println!("   Expected Fee: {} {}", leg.expected_fee, symbol);
+    println!("   Actual Fee: {} {}", leg.actual_fee, symbol);
+    println!("   Match: {}", if leg.actual_fee == leg.expected_fee { "✅ Yes" } else { "❌ No" });
+    println!("   Events: {}", outcome.events.len());
+
+    save_run(&args, &config, &outcome);
+
+    if let Some(before) = reserve_before {
+        assert_repayment(
+            client.owner.clone(),
+            config.flash_loan_provider,
+            tusdt_address,
+            before,
+            leg.expected_fee.raw(),
+            config.max_fee_tolerance,
+        )
+        .await?;
+        println!("   Repayment: ✅ Provider reserve grew by the fee");
     }
 
+    // Fail the run if the provider charged more than allowed
+    check_fee_paid(leg.expected_fee.raw(), leg.actual_fee.raw(), config.max_fee_tolerance)?;
+
     println!("\n{}", "=".repeat(60));
     println!("✨ Flash Loan Complete!");
     println!("{}", "=".repeat(60));
@@ -224,3 +502,19 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+// A reverted run is saved too, so it can be inspected afterwards
+fn save_run(args: &Args, config: &Config, outcome: &FlashLoanOutcome) {
+    if !args.save {
+        return;
+    }
+    match persist_run(config.network, outcome, &config.output_dir) {
+        Ok(path) => println!("\n💾 Saved: {}", path.display()),
+        Err(e) => println!("\n⚠️  Could not save the run: {:#}", e),
+    }
+}
+
+// U256's FromStr reads hex, so decimal base units are parsed explicitly
+fn parse_base_units(value: &str) -> Result<U256, String> {
+    U256::from_dec_str(value).map_err(|e| format!("not a base-unit integer: {}", e))
+}
@@ -0,0 +1,241 @@
+//! Self-describing balance assertions.
+//!
+//! `Expect::balance(token, holder)` starts an expectation; `at_least`,
+//! `exactly` or, after `capture`, `changed_by` complete it. A failed check
+//! names the holder, the token, the balance found and what was expected.
+
+use crate::{abi::IERC20, error::CapacitorError};
+use ethers::{
+    providers::Middleware,
+    types::{Address, I256, U256},
+};
+use eyre::Result;
+use std::{fmt, sync::Arc};
+
+type Failure = Box<dyn Fn(U256) -> CapacitorError + Send + Sync>;
+
+/// A token balance that an expectation is about.
+#[derive(Debug, Clone, Copy)]
+pub struct Balance {
+    token: Address,
+    holder: Address,
+}
+
+/// A balance captured now, to compare a later balance against.
+#[derive(Debug, Clone, Copy)]
+pub struct Captured {
+    balance: Balance,
+    before: U256,
+}
+
+/// A balance and the condition it has to meet.
+pub struct Expect {
+    balance: Balance,
+    condition: Condition,
+    failure: Option<Failure>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    AtLeast(U256),
+    Exactly(U256),
+    ChangedBy { before: U256, delta: I256 },
+}
+
+impl Expect {
+    /// Start an expectation about `holder`'s balance of `token`.
+    pub fn balance(token: Address, holder: Address) -> Balance {
+        Balance { token, holder }
+    }
+
+    /// Fail with `failure(actual)` instead of the generic
+    /// `CapacitorError::BalanceExpectation`.
+    pub fn or(mut self, failure: impl Fn(U256) -> CapacitorError + Send + Sync + 'static) -> Self {
+        self.failure = Some(Box::new(failure));
+        self
+    }
+
+    /// Read the balance and check it, returning the balance read.
+    pub async fn check<M: Middleware + 'static>(&self, client: Arc<M>) -> Result<U256> {
+        let actual = self.balance.read(client).await?;
+        self.verify(actual)?;
+        Ok(actual)
+    }
+
+    /// Check an already-read balance.
+    pub fn verify(&self, actual: U256) -> Result<(), CapacitorError> {
+        if self.condition.holds(actual) {
+            return Ok(());
+        }
+        Err(match &self.failure {
+            Some(failure) => failure(actual),
+            None => CapacitorError::BalanceExpectation {
+                holder: self.balance.holder,
+                token: self.balance.token,
+                actual,
+                expected: self.condition.to_string(),
+            },
+        })
+    }
+}
+
+impl fmt::Debug for Expect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expect")
+            .field("balance", &self.balance)
+            .field("condition", &self.condition)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Balance {
+    pub fn at_least(self, amount: U256) -> Expect {
+        self.expect(Condition::AtLeast(amount))
+    }
+
+    pub fn exactly(self, amount: U256) -> Expect {
+        self.expect(Condition::Exactly(amount))
+    }
+
+    /// Read the balance now, for a later [`Captured::changed_by`].
+    pub async fn capture<M: Middleware + 'static>(self, client: Arc<M>) -> Result<Captured> {
+        let before = self.read(client).await?;
+        Ok(Captured {
+            balance: self,
+            before,
+        })
+    }
+
+    async fn read<M: Middleware + 'static>(&self, client: Arc<M>) -> Result<U256> {
+        Ok(IERC20::new(self.token, client)
+            .balance_of(self.holder)
+            .await?)
+    }
+
+    fn expect(self, condition: Condition) -> Expect {
+        Expect {
+            balance: self,
+            condition,
+            failure: None,
+        }
+    }
+}
+
+impl Captured {
+    /// The balance when it was captured.
+    pub fn before(&self) -> U256 {
+        self.before
+    }
+
+    /// Expect the balance to have moved by exactly `delta` since capture.
+    pub fn changed_by(self, delta: I256) -> Expect {
+        self.balance.expect(Condition::ChangedBy {
+            before: self.before,
+            delta,
+        })
+    }
+}
+
+impl Condition {
+    fn holds(self, actual: U256) -> bool {
+        match self {
+            Condition::AtLeast(amount) => actual >= amount,
+            Condition::Exactly(amount) => actual == amount,
+            Condition::ChangedBy { before, delta } => {
+                let target = if delta.is_negative() {
+                    before.checked_sub(delta.unsigned_abs())
+                } else {
+                    before.checked_add(delta.into_raw())
+                };
+                target == Some(actual)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::AtLeast(amount) => write!(f, "at least {}", amount),
+            Condition::Exactly(amount) => write!(f, "exactly {}", amount),
+            Condition::ChangedBy { before, delta } if delta.is_negative() => {
+                write!(f, "{} down by {}", before, delta.unsigned_abs())
+            }
+            Condition::ChangedBy { before, delta } => {
+                write!(f, "{} up by {}", before, delta.into_raw())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: Address = Address::repeat_byte(0x01);
+    const HOLDER: Address = Address::repeat_byte(0x02);
+
+    fn changed_by(before: u64, delta: i64) -> Expect {
+        Captured {
+            balance: Expect::balance(TOKEN, HOLDER),
+            before: U256::from(before),
+        }
+        .changed_by(I256::from(delta))
+    }
+
+    #[test]
+    fn a_negative_change_is_measured_down_from_the_capture() {
+        let expect = changed_by(10, -3);
+        assert!(expect.verify(U256::from(7)).is_ok());
+        assert!(expect.verify(U256::from(13)).is_err());
+        assert!(changed_by(10, 3).verify(U256::from(13)).is_ok());
+    }
+
+    #[test]
+    fn a_change_past_zero_or_the_largest_balance_never_holds() {
+        let expect = changed_by(2, -5);
+        for actual in [U256::zero(), U256::from(2), U256::MAX] {
+            assert!(expect.verify(actual).is_err(), "{}", actual);
+        }
+
+        let at_max = Captured {
+            balance: Expect::balance(TOKEN, HOLDER),
+            before: U256::MAX,
+        }
+        .changed_by(I256::one());
+        assert!(at_max.verify(U256::MAX).is_err());
+        assert!(at_max.verify(U256::zero()).is_err());
+    }
+
+    #[test]
+    fn a_failure_says_what_was_expected() {
+        let err = changed_by(10, -3).verify(U256::from(9)).unwrap_err();
+        assert!(matches!(err, CapacitorError::BalanceExpectation { .. }));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{:?} holds 9 of token {:?}, expected 10 down by 3",
+                HOLDER, TOKEN
+            )
+        );
+
+        let err = Expect::balance(TOKEN, HOLDER)
+            .at_least(U256::from(5))
+            .verify(U256::from(4))
+            .unwrap_err();
+        assert!(err.to_string().ends_with("expected at least 5"), "{}", err);
+    }
+
+    #[test]
+    fn a_custom_failure_replaces_the_generic_one() {
+        let err = Expect::balance(TOKEN, HOLDER)
+            .exactly(U256::from(5))
+            .or(|_| CapacitorError::NoTokenBalance {
+                holder: HOLDER,
+                token: TOKEN,
+            })
+            .verify(U256::zero())
+            .unwrap_err();
+        assert!(matches!(err, CapacitorError::NoTokenBalance { .. }));
+    }
+}
@@ -0,0 +1,184 @@
+//! Failover across several RPC endpoints.
+//!
+//! `FailoverProvider` ranks its endpoints before every request: healthy
+//! endpoints first, fastest (by exponential moving average of response
+//! time) first among those. When an endpoint fails at the transport level
+//! (connection refused, timeout, HTTP error) the next one is tried and the
+//! failed one is marked unhealthy for a while. JSON-RPC error responses
+//! such as reverts come from a working node and are returned as-is.
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// Weight of the newest sample in the latency average.
+const EMA_ALPHA: f64 = 0.2;
+
+/// How long a failed endpoint is ranked last before it is tried first again.
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Clones share endpoint statistics.
+#[derive(Debug, Clone)]
+pub struct FailoverProvider<T> {
+    endpoints: Arc<[(String, T)]>,
+    health: Arc<Mutex<Vec<Health>>>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Health {
+    ema_latency: Option<Duration>,
+    requests: u64,
+    failures: u64,
+    failed_at: Option<Instant>,
+}
+
+impl Health {
+    fn is_healthy(&self) -> bool {
+        self.failed_at
+            .is_none_or(|failed_at| failed_at.elapsed() >= RETRY_AFTER)
+    }
+
+    fn record_success(&mut self, latency: Duration) {
+        self.requests += 1;
+        self.failed_at = None;
+        self.ema_latency = Some(match self.ema_latency {
+            Some(ema) => ema.mul_f64(1.0 - EMA_ALPHA) + latency.mul_f64(EMA_ALPHA),
+            None => latency,
+        });
+    }
+
+    fn record_failure(&mut self) {
+        self.requests += 1;
+        self.failures += 1;
+        self.failed_at = Some(Instant::now());
+    }
+}
+
+/// A snapshot of one endpoint's health.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointStat {
+    pub url: String,
+    /// Average response time; `None` until the endpoint has answered once.
+    pub ema_latency: Option<Duration>,
+    pub requests: u64,
+    pub failures: u64,
+    pub healthy: bool,
+}
+
+impl<T> FailoverProvider<T> {
+    /// `endpoints` are tried in the given order until latencies are known;
+    /// it must not be empty.
+    pub fn new(endpoints: Vec<(String, T)>) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "FailoverProvider needs at least one endpoint"
+        );
+        let health = vec![Health::default(); endpoints.len()];
+        Self {
+            endpoints: endpoints.into(),
+            health: Arc::new(Mutex::new(health)),
+        }
+    }
+
+    /// Label of the endpoint the next request will go to first.
+    pub fn current(&self) -> &str {
+        &self.endpoints[self.ranking()[0]].0
+    }
+
+    /// Per-endpoint statistics, in configured order.
+    pub fn endpoint_stats(&self) -> Vec<EndpointStat> {
+        let health = self.health.lock().expect("failover lock poisoned");
+        self.endpoints
+            .iter()
+            .zip(health.iter())
+            .map(|((url, _), health)| EndpointStat {
+                url: url.clone(),
+                ema_latency: health.ema_latency,
+                requests: health.requests,
+                failures: health.failures,
+                healthy: health.is_healthy(),
+            })
+            .collect()
+    }
+
+    // Healthy before unhealthy, then by latency. Endpoints not measured yet
+    // go first so they get a sample; ties keep the configured order.
+    fn ranking(&self) -> Vec<usize> {
+        let health = self.health.lock().expect("failover lock poisoned");
+        let mut order: Vec<usize> = (0..self.endpoints.len()).collect();
+        order.sort_by_key(|&i| {
+            let health = &health[i];
+            (!health.is_healthy(), health.ema_latency.unwrap_or_default())
+        });
+        order
+    }
+
+    fn health(&self, index: usize, update: impl FnOnce(&mut Health)) {
+        update(&mut self.health.lock().expect("failover lock poisoned")[index]);
+    }
+}
+
+impl<T: JsonRpcClient> FailoverProvider<T> {
+    /// Send `eth_blockNumber` to every endpoint to refresh its statistics.
+    pub async fn probe(&self) {
+        for (index, (url, endpoint)) in self.endpoints.iter().enumerate() {
+            let started = Instant::now();
+            let response: Result<Value, _> =
+                JsonRpcClient::request(endpoint, "eth_blockNumber", ()).await;
+            match response {
+                Err(e) if e.as_error_response().is_none() => {
+                    debug!(endpoint = %url, error = %e, "probe failed");
+                    self.health(index, Health::record_failure);
+                }
+                _ => self.health(index, |h| h.record_success(started.elapsed())),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient> JsonRpcClient for FailoverProvider<T> {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_error = None;
+
+        for index in self.ranking() {
+            let (url, endpoint) = &self.endpoints[index];
+            let started = Instant::now();
+            let response: Result<R, T::Error> =
+                JsonRpcClient::request(endpoint, method, &params).await;
+
+            match response {
+                Ok(response) => {
+                    self.health(index, |h| h.record_success(started.elapsed()));
+                    debug!(endpoint = %url, method, "served");
+                    return Ok(response);
+                }
+                Err(e) if e.as_error_response().is_some() => {
+                    self.health(index, |h| h.record_success(started.elapsed()));
+                    debug!(endpoint = %url, method, "served (rpc error)");
+                    return Err(e.into());
+                }
+                Err(e) => {
+                    self.health(index, Health::record_failure);
+                    debug!(endpoint = %url, method, error = %e, "endpoint failed, trying next");
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("at least one endpoint").into())
+    }
+}
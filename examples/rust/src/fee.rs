@@ -0,0 +1,72 @@
+//! Flash loan fee checks.
+
+use crate::error::CapacitorError;
+use ethers::types::U256;
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Highest fee accepted for `expected` with `tolerance_bps` of headroom.
+pub fn max_allowed_fee(expected: U256, tolerance_bps: u32) -> U256 {
+    expected * U256::from(BPS_DENOMINATOR + u64::from(tolerance_bps)) / U256::from(BPS_DENOMINATOR)
+}
+
+/// Fail if the fee actually charged is above `expected` plus the tolerance.
+pub fn check_fee_paid(
+    expected: U256,
+    charged: U256,
+    tolerance_bps: u32,
+) -> Result<(), CapacitorError> {
+    let max = max_allowed_fee(expected, tolerance_bps);
+    if charged > max {
+        return Err(CapacitorError::FeeExceeded {
+            expected,
+            max,
+            charged,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_exact_fee_passes() {
+        assert!(check_fee_paid(1_000.into(), 1_000.into(), 0).is_ok());
+        assert!(check_fee_paid(1_000.into(), 999.into(), 0).is_ok());
+    }
+
+    #[test]
+    fn one_unit_over_fails_without_tolerance() {
+        let err = check_fee_paid(1_000.into(), 1_001.into(), 0).unwrap_err();
+        assert_eq!(err.exit_code(), 4);
+        match err {
+            CapacitorError::FeeExceeded {
+                expected,
+                max,
+                charged,
+            } => {
+                assert_eq!(expected, U256::from(1_000));
+                assert_eq!(max, U256::from(1_000));
+                assert_eq!(charged, U256::from(1_001));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn tolerance_moves_the_boundary() {
+        // 50 bps on 10_000 allows up to 10_050
+        assert_eq!(max_allowed_fee(10_000.into(), 50), U256::from(10_050));
+        assert!(check_fee_paid(10_000.into(), 10_050.into(), 50).is_ok());
+        assert!(check_fee_paid(10_000.into(), 10_051.into(), 50).is_err());
+    }
+
+    #[test]
+    fn tolerance_rounds_down() {
+        // 1 bps of 9_999 is below one unit, so no headroom is added
+        assert_eq!(max_allowed_fee(9_999.into(), 1), U256::from(9_999));
+        assert!(check_fee_paid(9_999.into(), 10_000.into(), 1).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! Reading the fee from the provider itself.
+//!
+//! Provider versions name their fee function differently. `FeeResolver`
+//! tries each known signature with `eth_call` and remembers the first one
+//! that answers, so `fee_for` works whichever version is deployed.
+//!
+//! `fee_info` also reports the rate in bps. A rate read from
+//! `flashLoanFeeBps()` or `getFee()` does not depend on the amount, so it
+//! is cached per provider and token, and later amounts are priced locally
+//! without another call. `flashFee` may charge a minimum or round, so it is
+//! asked again for every amount.
+
+use crate::{error::CapacitorError, fee::BPS_DENOMINATOR, raw::call_raw};
+use ethers::{
+    abi::Token,
+    providers::{Middleware, MiddlewareError},
+    types::{Address, U256},
+};
+use eyre::{eyre, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tracing::debug;
+
+/// Rate assumed for a provider that exposes no fee function (0.01%).
+pub const FALLBACK_FEE_BPS: u32 = 1;
+
+/// A fee function the provider may expose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeFn {
+    /// ERC-3156 `flashFee(token, amount)`, returning the fee itself.
+    FlashFee,
+    /// `flashLoanFeeBps()`, returning the rate in bps.
+    FlashLoanFeeBps,
+    /// `getFee()`, returning the rate in bps.
+    GetFee,
+}
+
+impl FeeFn {
+    /// Probed in this order.
+    pub const CANDIDATES: [FeeFn; 3] = [FeeFn::FlashFee, FeeFn::FlashLoanFeeBps, FeeFn::GetFee];
+
+    pub fn signature(self) -> &'static str {
+        match self {
+            FeeFn::FlashFee => {
+                "function flashFee(address token, uint256 amount) view returns (uint256)"
+            }
+            FeeFn::FlashLoanFeeBps => "function flashLoanFeeBps() view returns (uint256)",
+            FeeFn::GetFee => "function getFee() view returns (uint256)",
+        }
+    }
+
+    /// Fee the provider at `provider` charges for borrowing `amount` of `token`.
+    pub async fn fee_for<M: Middleware>(
+        self,
+        client: &M,
+        provider: Address,
+        token: Address,
+        amount: U256,
+    ) -> Result<U256>
+    where
+        M::Error: 'static,
+    {
+        let value = self.query(client, provider, token, amount).await?;
+        Ok(match self {
+            FeeFn::FlashFee => value,
+            FeeFn::FlashLoanFeeBps | FeeFn::GetFee => bps_of(value, amount),
+        })
+    }
+
+    /// Rate and fee for borrowing `amount` of `token`.
+    ///
+    /// `flashFee` only returns the fee, so its rate is derived from
+    /// `amount`; pass a realistic amount so the rounding is small.
+    pub async fn fee_info<M: Middleware>(
+        self,
+        client: &M,
+        provider: Address,
+        token: Address,
+        amount: U256,
+    ) -> Result<FeeInfo>
+    where
+        M::Error: 'static,
+    {
+        let value = self.query(client, provider, token, amount).await?;
+        let bps = match self {
+            FeeFn::FlashFee if amount.is_zero() => U256::zero(),
+            FeeFn::FlashFee => value * U256::from(BPS_DENOMINATOR) / amount,
+            FeeFn::FlashLoanFeeBps | FeeFn::GetFee => value,
+        };
+        let bps = u32::try_from(bps)
+            .map_err(|_| eyre!("{} implies a rate of {} bps", self.signature(), bps))?;
+        Ok(match self {
+            FeeFn::FlashFee => FeeInfo {
+                bps,
+                absolute: value,
+            },
+            FeeFn::FlashLoanFeeBps | FeeFn::GetFee => FeeInfo::from_bps(bps, amount),
+        })
+    }
+
+    async fn query<M: Middleware>(
+        self,
+        client: &M,
+        provider: Address,
+        token: Address,
+        amount: U256,
+    ) -> Result<U256>
+    where
+        M::Error: 'static,
+    {
+        let args = match self {
+            FeeFn::FlashFee => vec![Token::Address(token), Token::Uint(amount)],
+            FeeFn::FlashLoanFeeBps | FeeFn::GetFee => Vec::new(),
+        };
+        let output = call_raw(client, provider, self.signature(), args).await?;
+        match output.as_slice() {
+            [Token::Uint(value)] => Ok(*value),
+            _ => Err(eyre!("{} returned {:?}", self.signature(), output)),
+        }
+    }
+}
+
+/// A provider's fee for one loan, as a rate and as an amount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeInfo {
+    pub bps: u32,
+    /// Fee in the token's base units.
+    pub absolute: U256,
+}
+
+impl FeeInfo {
+    /// Fee for `amount` at `bps`.
+    pub fn from_bps(bps: u32, amount: U256) -> Self {
+        Self {
+            bps,
+            absolute: bps_of(U256::from(bps), amount),
+        }
+    }
+
+    /// Fee for `amount` at [`FALLBACK_FEE_BPS`].
+    pub fn fallback(amount: U256) -> Self {
+        Self::from_bps(FALLBACK_FEE_BPS, amount)
+    }
+}
+
+fn bps_of(bps: U256, amount: U256) -> U256 {
+    amount * bps / U256::from(BPS_DENOMINATOR)
+}
+
+/// Finds and caches each provider's fee function and rates.
+#[derive(Debug)]
+pub struct FeeResolver<M> {
+    client: Arc<M>,
+    probe_token: Address,
+    resolved: Mutex<HashMap<Address, FeeFn>>,
+    /// Rate in bps per (provider, token), for providers that report one.
+    rates: Mutex<HashMap<(Address, Address), u32>>,
+}
+
+impl<M: Middleware> FeeResolver<M>
+where
+    M::Error: 'static,
+{
+    /// `probe_token` is passed to `flashFee` while probing, since ERC-3156
+    /// providers revert for tokens they do not lend.
+    pub fn new(client: Arc<M>, probe_token: Address) -> Self {
+        Self {
+            client,
+            probe_token,
+            resolved: Mutex::new(HashMap::new()),
+            rates: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The fee function `provider` exposes, probed on first use.
+    ///
+    /// Fails with `CapacitorError::Unsupported` if none of
+    /// [`FeeFn::CANDIDATES`] answers. Transport errors are returned as-is
+    /// rather than counted as a missing function.
+    pub async fn resolve(&self, provider: Address) -> Result<FeeFn> {
+        if let Some(fee_fn) = self.cached(provider) {
+            return Ok(fee_fn);
+        }
+
+        let amount = U256::from(BPS_DENOMINATOR);
+        for candidate in FeeFn::CANDIDATES {
+            match candidate
+                .fee_for(&*self.client, provider, self.probe_token, amount)
+                .await
+            {
+                Ok(_) => {
+                    self.resolved
+                        .lock()
+                        .expect("fee resolver lock poisoned")
+                        .insert(provider, candidate);
+                    return Ok(candidate);
+                }
+                Err(e) if is_transport_error::<M>(&e) => return Err(e),
+                Err(e) => debug!(signature = candidate.signature(), error = %e, "fee probe failed"),
+            }
+        }
+
+        let tried: Vec<_> = FeeFn::CANDIDATES.iter().map(|f| f.signature()).collect();
+        Err(CapacitorError::Unsupported(format!(
+            "provider {:?} exposes no known fee function (tried {})",
+            provider,
+            tried.join("; ")
+        ))
+        .into())
+    }
+
+    /// Fee for borrowing `amount` of `token`, using `provider`'s own function.
+    pub async fn fee_for(&self, provider: Address, token: Address, amount: U256) -> Result<U256> {
+        let fee_fn = self.resolve(provider).await?;
+        fee_fn.fee_for(&*self.client, provider, token, amount).await
+    }
+
+    /// Rate and fee for borrowing `amount` of `token` from `provider`.
+    ///
+    /// For a provider that reports its rate, only the first call per token
+    /// reaches the node and later amounts are priced from the cached rate.
+    /// A `flashFee` provider is asked for each amount. Errors as
+    /// [`Self::resolve`].
+    pub async fn fee_info(
+        &self,
+        provider: Address,
+        token: Address,
+        amount: U256,
+    ) -> Result<FeeInfo> {
+        let cached = self
+            .rates
+            .lock()
+            .expect("fee resolver lock poisoned")
+            .get(&(provider, token))
+            .copied();
+        if let Some(bps) = cached {
+            return Ok(FeeInfo::from_bps(bps, amount));
+        }
+
+        let fee_fn = self.resolve(provider).await?;
+        let info = fee_fn
+            .fee_info(&*self.client, provider, token, amount)
+            .await?;
+        // The bps derived from flashFee is truncated, and wrong for any
+        // other amount if the fee isn't proportional
+        if fee_fn != FeeFn::FlashFee {
+            self.rates
+                .lock()
+                .expect("fee resolver lock poisoned")
+                .insert((provider, token), info.bps);
+        }
+        Ok(info)
+    }
+
+    /// Like [`Self::fee_info`], but falls back to [`FeeInfo::fallback`] when
+    /// the provider exposes no known fee function.
+    pub async fn fee_info_or_fallback(
+        &self,
+        provider: Address,
+        token: Address,
+        amount: U256,
+    ) -> Result<FeeInfo> {
+        match self.fee_info(provider, token, amount).await {
+            Err(e) if matches!(e.downcast_ref(), Some(CapacitorError::Unsupported(_))) => {
+                debug!(?provider, "no fee function, assuming the fallback rate");
+                Ok(FeeInfo::fallback(amount))
+            }
+            result => result,
+        }
+    }
+
+    fn cached(&self, provider: Address) -> Option<FeeFn> {
+        self.resolved
+            .lock()
+            .expect("fee resolver lock poisoned")
+            .get(&provider)
+            .copied()
+    }
+}
+
+/// Whether `error` never reached a node, as opposed to a revert or
+/// undecodable output.
+///
+/// For a probe, a revert means "not this function"; a transport error means
+/// the probe proved nothing.
+pub(crate) fn is_transport_error<M: Middleware>(error: &eyre::Report) -> bool
+where
+    M::Error: 'static,
+{
+    error
+        .downcast_ref::<M::Error>()
+        .is_some_and(|e| e.as_error_response().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{revert_error, MockNode};
+    use ethers::{providers::Provider, utils::id};
+    use serde_json::Value;
+
+    const PROVIDER: Address = Address::repeat_byte(0xfe);
+    const TOKEN: Address = Address::repeat_byte(0x01);
+
+    fn selector(signature: &str) -> String {
+        format!("0x{}", hex::encode(&id(signature)[..4]))
+    }
+
+    fn word(value: U256) -> Value {
+        Value::from(format!("0x{:064x}", value))
+    }
+
+    // A provider answering `signature`, with `flashFee` charging 0.1% plus
+    // a flat 7 base units
+    async fn node(signature: &'static str) -> MockNode {
+        MockNode::start(move |method, params| match method {
+            "eth_chainId" => Ok(Value::from("0x2612")),
+            "eth_call" => {
+                let data = params[0]["data"]
+                    .as_str()
+                    .or_else(|| params[0]["input"].as_str())
+                    .unwrap_or_default();
+                if !data.starts_with(&selector(signature)) {
+                    return Err(revert_error("0x"));
+                }
+                Ok(match signature {
+                    "flashFee(address,uint256)" => {
+                        let amount = U256::from_str_radix(&data[74..138], 16).unwrap();
+                        word(amount / 1000 + 7)
+                    }
+                    _ => word(U256::from(9)),
+                })
+            }
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap()
+    }
+
+    fn resolver(node: &MockNode) -> FeeResolver<Provider<ethers::providers::Http>> {
+        let provider = Provider::try_from(node.url()).unwrap();
+        FeeResolver::new(Arc::new(provider), TOKEN)
+    }
+
+    #[tokio::test]
+    async fn flash_fee_is_asked_for_every_amount() {
+        let node = node("flashFee(address,uint256)").await;
+        let fees = resolver(&node);
+
+        for amount in [10_000u64, 1_000_000, 50] {
+            let amount = U256::from(amount);
+            let info = fees.fee_info(PROVIDER, TOKEN, amount).await.unwrap();
+            assert_eq!(info.absolute, amount / 1000 + 7);
+        }
+        // One probe, then one call per amount
+        assert_eq!(node.count("eth_call"), 4);
+    }
+
+    #[tokio::test]
+    async fn a_bps_rate_is_read_once_per_token() {
+        let node = node("flashLoanFeeBps()").await;
+        let fees = resolver(&node);
+
+        for amount in [10_000u64, 1_000_000] {
+            let info = fees.fee_info(PROVIDER, TOKEN, amount.into()).await.unwrap();
+            assert_eq!(info, FeeInfo::from_bps(9, amount.into()));
+        }
+        // flashFee reverts, flashLoanFeeBps answers the probe and the rate
+        assert_eq!(node.count("eth_call"), 3);
+    }
+}
@@ -0,0 +1,342 @@
+//! Running a flash loan through the deployed tester.
+//!
+//! `execute_flash_loan` sends the loan and reports what happened without
+//! printing anything, so it can be driven from the binaries or from other
+//! code alike. `execute_flash_loan_multi` borrows several tokens in one
+//! transaction through a tester that implements `executeFlashLoanBatch`.
+
+use crate::{
+    abi::{ExecuteFlashLoanBatchCall, IFlashLoanTester, IERC20},
+    amount::TokenAmount,
+    balances::snapshot_balances,
+    client::FlashLoanClient,
+    deployment::bytecode_has_selector,
+    error::CapacitorError,
+    events::{decode_log, FlashLoanExecuted},
+    notify::notify_run,
+    send::TxBuilder,
+};
+use ethers::{contract::EthCall, prelude::*, types::transaction::eip2930::AccessList};
+use eyre::Result;
+use serde::Serialize;
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// `FlashLoanTester.TestMode`, in contract order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashLoanMode {
+    /// Repay the loan plus fee.
+    Success,
+    /// Return `false` from the callback without repaying.
+    FailNoRepay,
+    /// Repay the principal but not the fee.
+    FailPartial,
+}
+
+impl FlashLoanMode {
+    pub const ALL: [FlashLoanMode; 3] = [
+        FlashLoanMode::Success,
+        FlashLoanMode::FailNoRepay,
+        FlashLoanMode::FailPartial,
+    ];
+
+    /// Whether the provider should accept a loan run in this mode.
+    pub fn should_succeed(self) -> bool {
+        self == FlashLoanMode::Success
+    }
+
+    /// Words, lowercase, of which the provider's revert reason for this
+    /// mode should contain at least one. `None` for `Success`.
+    ///
+    /// `FAIL_NO_REPAY` is rejected because the callback returned `false`,
+    /// `FAIL_PARTIAL` because the fee never came back. A revert from the
+    /// tester itself ("Not owner", "Transfer failed") matches neither.
+    pub fn expected_revert(self) -> Option<&'static [&'static str]> {
+        match self {
+            FlashLoanMode::Success => None,
+            FlashLoanMode::FailNoRepay => Some(&["callback", "executeoperation", "returned false"]),
+            FlashLoanMode::FailPartial => Some(&["repay", "repaid", "fee"]),
+        }
+    }
+}
+
+impl From<FlashLoanMode> for u8 {
+    fn from(mode: FlashLoanMode) -> u8 {
+        mode as u8
+    }
+}
+
+impl fmt::Display for FlashLoanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FlashLoanMode::Success => "SUCCESS",
+            FlashLoanMode::FailNoRepay => "FAIL_NO_REPAY",
+            FlashLoanMode::FailPartial => "FAIL_PARTIAL",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FlashLoanParams {
+    /// Deployed FlashLoanTester, owned by the client's owner.
+    pub tester: Address,
+    pub token: Address,
+    pub amount: TokenAmount,
+    /// The provider's fee for `amount`, from `FeeResolver`.
+    pub expected_fee: TokenAmount,
+    /// FlashLoanTester mode; see `FlashLoanMode`.
+    pub mode: u8,
+    /// Sent with the loan when set; see `access_list::generate_access_list`.
+    pub access_list: Option<AccessList>,
+}
+
+/// The figures for a single borrowed token.
+#[derive(Debug, Clone, Serialize)]
+pub struct LoanLeg {
+    pub token: Address,
+    pub amount: TokenAmount,
+    pub expected_fee: TokenAmount,
+    /// Drop in the tester's token balance across the loan.
+    pub actual_fee: TokenAmount,
+    pub balance_before: TokenAmount,
+    pub balance_after: TokenAmount,
+}
+
+impl LoanLeg {
+    fn new(
+        token: Address,
+        amount: TokenAmount,
+        expected_fee: TokenAmount,
+        before: U256,
+        after: U256,
+    ) -> Self {
+        Self {
+            token,
+            amount,
+            expected_fee,
+            actual_fee: amount.with_raw(before.saturating_sub(after)),
+            balance_before: amount.with_raw(before),
+            balance_after: amount.with_raw(after),
+        }
+    }
+}
+
+/// The fee and balance fields are summed over `legs`. They are that
+/// token's own figures for a single-token loan, and `None` for a batch
+/// whose tokens have different decimals; look at `legs` then.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashLoanOutcome {
+    pub tx_hash: H256,
+    pub block_number: Option<u64>,
+    pub gas_used: U256,
+    /// Whether the transaction succeeded (receipt status 1).
+    pub success: bool,
+    /// Seconds from sending the loan until its receipt was in.
+    pub latency_secs: f64,
+    pub expected_fee: Option<TokenAmount>,
+    pub actual_fee: Option<TokenAmount>,
+    pub balance_before: Option<TokenAmount>,
+    pub balance_after: Option<TokenAmount>,
+    pub legs: Vec<LoanLeg>,
+    /// Flash loan events found in the receipt.
+    pub events: Vec<FlashLoanExecuted>,
+    pub receipt: TransactionReceipt,
+}
+
+impl FlashLoanOutcome {
+    pub(crate) fn new(
+        tx_hash: H256,
+        receipt: TransactionReceipt,
+        latency: Duration,
+        legs: Vec<LoanLeg>,
+    ) -> Self {
+        let total = |field: fn(&LoanLeg) -> TokenAmount| {
+            let mut amounts = legs.iter().map(field);
+            let first = amounts.next()?;
+            amounts.try_fold(first, |sum, amount| sum.try_add(amount).ok())
+        };
+        Self {
+            tx_hash,
+            block_number: receipt.block_number.map(|block| block.as_u64()),
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            success: receipt.status == Some(U64::from(1)),
+            latency_secs: latency.as_secs_f64(),
+            expected_fee: total(|leg| leg.expected_fee),
+            actual_fee: total(|leg| leg.actual_fee),
+            balance_before: total(|leg| leg.balance_before),
+            balance_after: total(|leg| leg.balance_after),
+            events: receipt.logs.iter().filter_map(decode_log).collect(),
+            legs,
+            receipt,
+        }
+    }
+}
+
+/// Send `testFlashLoan` from the owner and wait for it to be mined.
+///
+/// The tester must already hold enough tokens to pay the fee. A reverted
+/// transaction is reported through `success` rather than as an error.
+pub async fn execute_flash_loan(
+    client: &FlashLoanClient,
+    params: &FlashLoanParams,
+) -> Result<FlashLoanOutcome> {
+    recorded(client, send_flash_loan(client, params)).await
+}
+
+async fn send_flash_loan(
+    client: &FlashLoanClient,
+    params: &FlashLoanParams,
+) -> Result<FlashLoanOutcome> {
+    let token = IERC20::new(params.token, client.owner.clone());
+    let tester = IFlashLoanTester::new(params.tester, client.owner.clone());
+
+    let balance_before = token.balance_of(params.tester).await?;
+
+    let call = tester.test_flash_loan(params.token, params.amount.raw(), params.mode);
+    let mut send = TxBuilder::call(client, &client.owner, call);
+    if let Some(list) = &params.access_list {
+        send = send.access_list(list.clone());
+    }
+    let sent = Instant::now();
+    let receipt = send.send_and_confirm().await?;
+    let latency = sent.elapsed();
+    let tx_hash = receipt.transaction_hash;
+
+    let balance_after = token.balance_of(params.tester).await?;
+
+    let leg = LoanLeg::new(
+        params.token,
+        params.amount,
+        params.expected_fee,
+        balance_before,
+        balance_after,
+    );
+    Ok(FlashLoanOutcome::new(tx_hash, receipt, latency, vec![leg]))
+}
+
+/// Borrow every `tokens[i]` for `amounts[i]` in one `executeFlashLoanBatch`.
+///
+/// `expected_fees[i]` is the provider's fee for `amounts[i]`, and the
+/// tester must already hold it. Fails with
+/// `CapacitorError::Unsupported` when the deployed tester has no batch
+/// entry point, and with `LengthMismatch` when the slices differ in length.
+pub async fn execute_flash_loan_multi(
+    client: &FlashLoanClient,
+    tester: Address,
+    tokens: &[Address],
+    amounts: &[TokenAmount],
+    expected_fees: &[TokenAmount],
+    mode: u8,
+    params: Bytes,
+) -> Result<FlashLoanOutcome> {
+    let run = send_flash_loan_multi(client, tester, tokens, amounts, expected_fees, mode, params);
+    recorded(client, run).await
+}
+
+async fn send_flash_loan_multi(
+    client: &FlashLoanClient,
+    tester: Address,
+    tokens: &[Address],
+    amounts: &[TokenAmount],
+    expected_fees: &[TokenAmount],
+    mode: u8,
+    params: Bytes,
+) -> Result<FlashLoanOutcome> {
+    for len in [amounts.len(), expected_fees.len()] {
+        if len != tokens.len() {
+            return Err(CapacitorError::LengthMismatch {
+                tokens: tokens.len(),
+                amounts: len,
+            }
+            .into());
+        }
+    }
+    if !supports_batch(client, tester).await? {
+        return Err(CapacitorError::Unsupported(format!(
+            "{:?} does not implement executeFlashLoanBatch",
+            tester
+        ))
+        .into());
+    }
+
+    let before = snapshot_balances(client.owner.clone(), tester, tokens).await?;
+
+    let contract = IFlashLoanTester::new(tester, client.owner.clone());
+    let raw = amounts.iter().map(|amount| amount.raw()).collect();
+    let call = contract.execute_flash_loan_batch(tokens.to_vec(), raw, mode, params);
+    let sent = Instant::now();
+    let receipt = TxBuilder::call(client, &client.owner, call)
+        .send_and_confirm()
+        .await?;
+    let latency = sent.elapsed();
+    let tx_hash = receipt.transaction_hash;
+
+    let after = snapshot_balances(client.owner.clone(), tester, tokens).await?;
+
+    let legs = tokens
+        .iter()
+        .zip(amounts.iter().zip(expected_fees))
+        .map(|(&token, (&amount, &fee))| {
+            LoanLeg::new(token, amount, fee, before[&token], after[&token])
+        })
+        .collect();
+    Ok(FlashLoanOutcome::new(tx_hash, receipt, latency, legs))
+}
+
+// Feeds the run into the metrics registry when that feature is built, and
+// reports it to the webhook when one is configured.
+async fn recorded(
+    client: &FlashLoanClient,
+    run: impl std::future::Future<Output = Result<FlashLoanOutcome>>,
+) -> Result<FlashLoanOutcome> {
+    let result = run.await;
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_run(&result);
+    notify_run(client.webhook_url.as_deref(), &result).await;
+    result
+}
+
+/// Whether the code at `tester` dispatches `executeFlashLoanBatch`; see
+/// [`bytecode_has_selector`].
+pub async fn supports_batch(client: &FlashLoanClient, tester: Address) -> Result<bool> {
+    let code = client.provider.get_code(tester, None).await?;
+    Ok(bytecode_has_selector(
+        &code,
+        ExecuteFlashLoanBatchCall::selector(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(token: u8, amount: &str, decimals: u8) -> LoanLeg {
+        let amount = TokenAmount::from_human(amount, decimals).unwrap();
+        let before = amount.raw();
+        let fee = amount.with_raw(before / 10_000);
+        LoanLeg::new(Address::repeat_byte(token), amount, fee, before, before / 2)
+    }
+
+    fn outcome(legs: Vec<LoanLeg>) -> FlashLoanOutcome {
+        FlashLoanOutcome::new(H256::zero(), Default::default(), Duration::ZERO, legs)
+    }
+
+    #[test]
+    fn totals_sum_legs_with_the_same_decimals() {
+        let outcome = outcome(vec![leg(1, "10", 6), leg(2, "30", 6)]);
+        let balance_before = outcome.balance_before.unwrap();
+        assert_eq!(balance_before.raw(), U256::from(40_000_000));
+        assert_eq!(balance_before.decimals(), 6);
+        assert_eq!(outcome.actual_fee.unwrap().raw(), U256::from(20_000_000));
+    }
+
+    #[test]
+    fn totals_are_left_out_across_decimals() {
+        let outcome = outcome(vec![leg(1, "10", 6), leg(2, "10", 18)]);
+        assert!(outcome.expected_fee.is_none());
+        assert!(outcome.balance_after.is_none());
+        assert_eq!(outcome.legs[1].amount.decimals(), 18);
+    }
+}
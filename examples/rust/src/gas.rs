@@ -0,0 +1,385 @@
+//! Gas limits for outgoing transactions.
+//!
+//! Estimates can come in low when the flash loan callback does more work
+//! than the node simulated, so they are padded by a buffer before being
+//! set. The padded limit is clamped to the block gas limit, since a limit
+//! above it can never be mined. An explicit `gas_limit` skips estimation;
+//! one above the block gas limit is an error rather than silently lowered.
+//!
+//! Estimation can also revert for a transaction that would succeed, e.g.
+//! when the callback depends on state the node's simulation doesn't see.
+//! A `fallback` limit is used in that case; without one the revert is
+//! returned. Any other failure, such as an unreachable node, is returned
+//! whether or not a fallback is set.
+
+use crate::error::CapacitorError;
+use ethers::{
+    contract::EthError,
+    providers::{Middleware, MiddlewareError},
+    types::{transaction::eip2718::TypedTransaction, BlockNumber, U256},
+};
+use eyre::{eyre, Result};
+use tracing::warn;
+
+pub const DEFAULT_GAS_BUFFER: f64 = 1.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasPolicy {
+    /// Fixed limit used instead of an estimate.
+    pub limit: Option<u64>,
+    /// Multiplier applied to estimates.
+    pub buffer: f64,
+    /// Limit used when estimation reverts.
+    pub fallback: Option<u64>,
+}
+
+impl Default for GasPolicy {
+    fn default() -> Self {
+        Self {
+            limit: None,
+            buffer: DEFAULT_GAS_BUFFER,
+            fallback: None,
+        }
+    }
+}
+
+/// Set the gas limit on `tx` and return it.
+///
+/// `override_limit` wins when given, and fails with
+/// `CapacitorError::GasLimitTooHigh` if it is above `block_gas_limit`.
+/// Otherwise `estimate * buffer` is used, capped at `block_gas_limit`.
+pub fn apply_gas_limit(
+    tx: &mut TypedTransaction,
+    estimate: U256,
+    buffer: f64,
+    override_limit: Option<U256>,
+    block_gas_limit: U256,
+) -> Result<U256, CapacitorError> {
+    let limit = match override_limit {
+        Some(limit) if limit > block_gas_limit => {
+            return Err(CapacitorError::GasLimitTooHigh {
+                limit,
+                block_gas_limit,
+            })
+        }
+        Some(limit) => limit,
+        None => buffered(estimate, buffer).min(block_gas_limit),
+    };
+    tx.set_gas(limit);
+    Ok(limit)
+}
+
+// Computed in thousandths so the buffer can be applied to a U256
+fn buffered(estimate: U256, buffer: f64) -> U256 {
+    let per_mille = (buffer.max(1.0) * 1000.0).round() as u64;
+    estimate.saturating_mul(U256::from(per_mille)) / U256::from(1000)
+}
+
+/// Estimate (unless `policy` fixes the limit) and set the gas limit on `tx`.
+///
+/// `client` should be the signer that will send `tx`, so the estimate runs
+/// from the right account.
+pub async fn fill_gas_limit<M: Middleware>(
+    client: &M,
+    tx: &mut TypedTransaction,
+    policy: GasPolicy,
+) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    let block = client
+        .get_block(BlockNumber::Latest)
+        .await?
+        .ok_or_else(|| eyre!("latest block not available"))?;
+
+    if let Some(limit) = policy.limit {
+        let limit = Some(U256::from(limit));
+        return Ok(apply_gas_limit(
+            tx,
+            U256::zero(),
+            policy.buffer,
+            limit,
+            block.gas_limit,
+        )?);
+    }
+
+    let estimate = match client.estimate_gas(tx, None).await {
+        Ok(estimate) => estimate,
+        Err(e) => {
+            let reason = estimate_failure(&e);
+            let reverted = e.as_error_response().is_some_and(|r| r.is_revert());
+            let Some(limit) = policy.fallback.filter(|_| reverted) else {
+                return Err(eyre::Report::new(e).wrap_err(reason));
+            };
+            warn!(limit, %reason, "gas estimation skipped, sending with the fallback limit");
+            let limit = Some(U256::from(limit));
+            return Ok(apply_gas_limit(
+                tx,
+                U256::zero(),
+                policy.buffer,
+                limit,
+                block.gas_limit,
+            )?);
+        }
+    };
+
+    Ok(apply_gas_limit(
+        tx,
+        estimate,
+        policy.buffer,
+        None,
+        block.gas_limit,
+    )?)
+}
+
+// The decoded `Error(string)` when estimation reverted
+fn estimate_failure<E: MiddlewareError>(error: &E) -> String {
+    let Some(response) = error.as_error_response().filter(|r| r.is_revert()) else {
+        return "gas estimation failed".to_string();
+    };
+    let data = response.as_revert_data().unwrap_or_default();
+    match String::decode_with_selector(&data) {
+        Some(reason) => format!("gas estimation reverted: {}", reason),
+        None => "gas estimation reverted".to_string(),
+    }
+}
+
+/// The limit `policy` would set for `tx`, and the cost in wei of sending
+/// `tx` with it at the current gas price.
+pub async fn estimate_limit_and_cost<M: Middleware>(
+    client: &M,
+    tx: &TypedTransaction,
+    policy: GasPolicy,
+) -> Result<(U256, U256)>
+where
+    M::Error: 'static,
+{
+    let mut tx = tx.clone();
+    let limit = fill_gas_limit(client, &mut tx, policy).await?;
+    let gas_price = client.get_gas_price().await?;
+    Ok((limit, limit.saturating_mul(gas_price)))
+}
+
+/// Cost in wei of sending `tx` with the limit `policy` would set, at the
+/// current gas price.
+pub async fn estimate_cost<M: Middleware>(
+    client: &M,
+    tx: &TypedTransaction,
+    policy: GasPolicy,
+) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    Ok(estimate_limit_and_cost(client, tx, policy).await?.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{revert_error, MockNode};
+    use ethers::{
+        providers::{Http, JsonRpcError, Provider},
+        types::TransactionRequest,
+    };
+    use serde_json::{json, Value};
+
+    const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+    fn tx() -> TypedTransaction {
+        TransactionRequest::new().into()
+    }
+
+    #[test]
+    fn estimates_are_padded_by_the_buffer() {
+        let mut tx = tx();
+        let limit =
+            apply_gas_limit(&mut tx, 100_000.into(), 1.25, None, BLOCK_GAS_LIMIT.into()).unwrap();
+        assert_eq!(limit, U256::from(125_000));
+        assert_eq!(tx.gas(), Some(&limit));
+    }
+
+    #[test]
+    fn buffer_math() {
+        assert_eq!(buffered(100_000.into(), 1.0), U256::from(100_000));
+        assert_eq!(buffered(100_000.into(), 1.5), U256::from(150_000));
+        // Thousandths are kept, anything finer is rounded
+        assert_eq!(buffered(1_000.into(), 1.0015), U256::from(1_002));
+        // A buffer below 1 never shrinks the estimate
+        assert_eq!(buffered(100_000.into(), 0.5), U256::from(100_000));
+        assert_eq!(buffered(U256::MAX, 2.0), U256::MAX / 1000);
+    }
+
+    #[test]
+    fn padded_estimates_are_clamped_to_the_block_gas_limit() {
+        let mut tx = tx();
+        let limit = apply_gas_limit(
+            &mut tx,
+            29_000_000.into(),
+            1.25,
+            None,
+            BLOCK_GAS_LIMIT.into(),
+        )
+        .unwrap();
+        assert_eq!(limit, U256::from(BLOCK_GAS_LIMIT));
+    }
+
+    #[test]
+    fn an_override_is_used_as_is() {
+        let mut tx = tx();
+        let limit = apply_gas_limit(
+            &mut tx,
+            100_000.into(),
+            1.25,
+            Some(500_000.into()),
+            BLOCK_GAS_LIMIT.into(),
+        )
+        .unwrap();
+        assert_eq!(limit, U256::from(500_000));
+
+        // Exactly the block gas limit is still allowed
+        let limit = apply_gas_limit(
+            &mut tx,
+            U256::zero(),
+            1.25,
+            Some(BLOCK_GAS_LIMIT.into()),
+            BLOCK_GAS_LIMIT.into(),
+        )
+        .unwrap();
+        assert_eq!(limit, U256::from(BLOCK_GAS_LIMIT));
+    }
+
+    #[test]
+    fn an_override_above_the_block_gas_limit_is_rejected() {
+        let mut tx = tx();
+        let err = apply_gas_limit(
+            &mut tx,
+            U256::zero(),
+            1.25,
+            Some((BLOCK_GAS_LIMIT + 1).into()),
+            BLOCK_GAS_LIMIT.into(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, CapacitorError::GasLimitTooHigh { .. }));
+        assert_eq!(err.exit_code(), 2);
+        assert_eq!(tx.gas(), None);
+    }
+
+    async fn node(estimate: Option<u64>) -> MockNode {
+        MockNode::start(move |method, _| match method {
+            "eth_getBlockByNumber" => Ok(json!({
+                "number": "0x1",
+                "hash": format!("0x{}", "11".repeat(32)),
+                "gasLimit": format!("{:#x}", BLOCK_GAS_LIMIT),
+            })),
+            "eth_gasPrice" => Ok(Value::from("0x3")),
+            "eth_estimateGas" => match estimate {
+                Some(gas) => Ok(Value::from(format!("{:#x}", gas))),
+                None => Err(revert_error("0x")),
+            },
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap()
+    }
+
+    fn policy(limit: Option<u64>, fallback: Option<u64>) -> GasPolicy {
+        GasPolicy {
+            limit,
+            fallback,
+            ..GasPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn fill_pads_the_node_estimate() {
+        let node = node(Some(80_000)).await;
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+        let mut tx = tx();
+        let limit = fill_gas_limit(&provider, &mut tx, policy(None, None))
+            .await
+            .unwrap();
+        assert_eq!(limit, U256::from(100_000));
+    }
+
+    #[tokio::test]
+    async fn the_cost_is_priced_at_the_padded_limit() {
+        let node = node(Some(80_000)).await;
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+        let (limit, cost) = estimate_limit_and_cost(&provider, &tx(), policy(None, None))
+            .await
+            .unwrap();
+        assert_eq!(limit, U256::from(100_000));
+        // Not the raw estimate of 80,000 at the gas price of 3
+        assert_eq!(cost, U256::from(300_000));
+    }
+
+    #[tokio::test]
+    async fn fill_uses_the_fallback_when_estimation_reverts() {
+        let node = node(None).await;
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+
+        let err = fill_gas_limit(&provider, &mut tx(), policy(None, None))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("gas estimation reverted"),
+            "{}",
+            err
+        );
+
+        let limit = fill_gas_limit(&provider, &mut tx(), policy(None, Some(400_000)))
+            .await
+            .unwrap();
+        assert_eq!(limit, U256::from(400_000));
+    }
+
+    #[tokio::test]
+    async fn the_fallback_is_not_used_when_estimation_fails_without_reverting() {
+        let node = MockNode::start(|method, _| match method {
+            "eth_getBlockByNumber" => Ok(json!({
+                "number": "0x1",
+                "hash": format!("0x{}", "11".repeat(32)),
+                "gasLimit": format!("{:#x}", BLOCK_GAS_LIMIT),
+            })),
+            "eth_estimateGas" => Err(JsonRpcError {
+                code: -32000,
+                message: "header not found".into(),
+                data: None,
+            }),
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+
+        let err = fill_gas_limit(&provider, &mut tx(), policy(None, Some(400_000)))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("gas estimation failed"), "{}", err);
+        assert!(
+            format!("{:#}", err).contains("header not found"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn a_fixed_limit_skips_estimation_but_not_the_cap() {
+        let node = node(Some(80_000)).await;
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+
+        let limit = fill_gas_limit(&provider, &mut tx(), policy(Some(600_000), None))
+            .await
+            .unwrap();
+        assert_eq!(limit, U256::from(600_000));
+        assert_eq!(node.count("eth_estimateGas"), 0);
+
+        let err = fill_gas_limit(&provider, &mut tx(), policy(Some(40_000_000), None))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(CapacitorError::GasLimitTooHigh { .. })
+        ));
+    }
+}
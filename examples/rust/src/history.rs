@@ -0,0 +1,74 @@
+//! Past flash loans from event logs.
+//!
+//! Nodes cap how many logs one `eth_getLogs` call may return, so the block
+//! range is walked in chunks. A chunk the node refuses as too large is
+//! halved and retried until it fits.
+
+use crate::events::{decode_log, FlashLoanExecuted, FlashLoanFilter};
+use ethers::{
+    contract::EthEvent,
+    providers::Middleware,
+    types::{Address, Filter, H256},
+};
+use eyre::{eyre, Result};
+
+/// Blocks requested per `eth_getLogs` call before any halving.
+pub const HISTORY_CHUNK_BLOCKS: u64 = 10_000;
+
+/// `FlashLoan` events emitted by `provider_addr` for `initiator` between
+/// `from_block` and `to_block` (inclusive), oldest first.
+pub async fn flash_loan_history<M: Middleware>(
+    client: &M,
+    provider_addr: Address,
+    initiator: Address,
+    from_block: u64,
+    to_block: u64,
+) -> Result<Vec<FlashLoanExecuted>> {
+    let base = Filter::new()
+        .address(provider_addr)
+        .topic0(FlashLoanFilter::signature())
+        .topic1(H256::from(initiator));
+
+    let mut events = Vec::new();
+    let mut chunk = HISTORY_CHUNK_BLOCKS;
+    let mut start = from_block;
+
+    while start <= to_block {
+        let end = start.saturating_add(chunk - 1).min(to_block);
+        let filter = base.clone().from_block(start).to_block(end);
+
+        match client.get_logs(&filter).await {
+            Ok(logs) => {
+                events.extend(logs.iter().filter_map(decode_log));
+                start = end + 1;
+            }
+            Err(e) if chunk > 1 && is_too_many_results(&e.to_string()) => {
+                chunk /= 2;
+            }
+            Err(e) => {
+                return Err(eyre!(
+                    "eth_getLogs failed for blocks {}..={}: {}",
+                    start,
+                    end,
+                    e
+                ));
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+// Wording differs between node implementations and RPC providers
+fn is_too_many_results(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "more than",
+        "too many",
+        "limit exceeded",
+        "range too large",
+        "block range",
+    ]
+    .iter()
+    .any(|needle| message.contains(needle))
+}
@@ -0,0 +1,75 @@
+//! Reconstructing a flash loan from its transaction hash.
+//!
+//! Everything `execute` reports about a loan can be read back from the
+//! chain later: the receipt says whether it succeeded and what gas it
+//! used, and its logs carry the flash loan events and fees.
+
+use crate::{
+    events::{decode_log, fees_paid, FlashLoanExecuted},
+    poll::TxStatus,
+};
+use ethers::{
+    providers::{JsonRpcClient, Middleware, Provider},
+    types::{Address, TransactionReceipt, H256, U256, U64},
+};
+use eyre::Result;
+use std::collections::BTreeMap;
+
+/// What the node knows about one transaction.
+#[derive(Debug, Clone)]
+pub struct TxInspection {
+    pub tx_hash: H256,
+    /// `Mined`, `Pending` when the node holds it unmined, or `NotFound`.
+    /// A single lookup can't tell a dropped transaction from one that was
+    /// never sent, so neither is reported as `Dropped`.
+    pub status: TxStatus,
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    /// Flash loan events in the receipt.
+    pub events: Vec<FlashLoanExecuted>,
+    /// Fee paid per token; see [`fees_paid`].
+    pub fees: BTreeMap<Address, U256>,
+}
+
+impl TxInspection {
+    pub fn receipt(&self) -> Option<&TransactionReceipt> {
+        match &self.status {
+            TxStatus::Mined(receipt) | TxStatus::Unconfirmed(receipt) => Some(receipt),
+            _ => None,
+        }
+    }
+
+    /// Whether the transaction succeeded (receipt status 1), once mined.
+    pub fn succeeded(&self) -> Option<bool> {
+        self.receipt()
+            .map(|receipt| receipt.status == Some(U64::from(1)))
+    }
+}
+
+/// Look up `tx_hash` and decode the flash loans it contains.
+pub async fn inspect_tx<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx_hash: H256,
+) -> Result<TxInspection> {
+    let tx = provider.get_transaction(tx_hash).await?;
+    let receipt = provider.get_transaction_receipt(tx_hash).await?;
+
+    let (status, events, fees) = match receipt {
+        Some(receipt) => {
+            let events = receipt.logs.iter().filter_map(decode_log).collect();
+            let fees = fees_paid(&receipt.logs);
+            (TxStatus::Mined(Box::new(receipt)), events, fees)
+        }
+        None if tx.is_some() => (TxStatus::Pending, Vec::new(), BTreeMap::new()),
+        None => (TxStatus::NotFound, Vec::new(), BTreeMap::new()),
+    };
+
+    Ok(TxInspection {
+        tx_hash,
+        status,
+        from: tx.as_ref().map(|tx| tx.from),
+        to: tx.as_ref().and_then(|tx| tx.to),
+        events,
+        fees,
+    })
+}
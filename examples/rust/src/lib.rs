@@ -0,0 +1,52 @@
+//! Shared building blocks for the flash loan example binaries.
+
+pub mod abi;
+pub mod access_list;
+pub mod amount;
+pub mod balances;
+pub mod batch;
+pub mod cancel;
+pub mod client;
+pub mod config;
+pub mod confirm;
+pub mod cost;
+pub mod deployment;
+pub mod error;
+pub mod events;
+pub mod expect;
+pub mod failover;
+pub mod fee;
+pub mod fee_resolver;
+pub mod flash_loan;
+pub mod gas;
+pub mod history;
+pub mod inspect;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod network;
+pub mod notify;
+pub mod ownership;
+pub mod persist;
+pub mod poll;
+pub mod pool;
+pub mod preflight;
+pub mod profit;
+pub mod rate_limit;
+pub mod raw;
+pub mod reconcile;
+pub mod record;
+pub mod relay;
+pub mod secret;
+pub mod self_test;
+pub mod send;
+pub mod signer;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tokens;
+pub mod tx;
+pub mod watch;
+
+pub use client::FlashLoanClient;
+pub use config::Config;
+pub use error::CapacitorError;
+pub use network::Network;
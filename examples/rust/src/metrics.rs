@@ -0,0 +1,270 @@
+//! Prometheus metrics for long-running commands.
+//!
+//! Counters and histograms live in process-wide atomics and are rendered in
+//! the Prometheus text format by a minimal HTTP endpoint started with
+//! [`serve`]. Only built with the `metrics` feature.
+
+use crate::flash_loan::FlashLoanOutcome;
+use ethers::types::{Address, U256};
+use eyre::Result;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+};
+
+const GAS_BUCKETS: [f64; 7] = [
+    50_000.0,
+    100_000.0,
+    200_000.0,
+    500_000.0,
+    1_000_000.0,
+    2_000_000.0,
+    5_000_000.0,
+];
+const LATENCY_BUCKETS: [f64; 7] = [1.0, 2.0, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+static LOANS_EXECUTED: AtomicU64 = AtomicU64::new(0);
+static LOAN_FAILURES: AtomicU64 = AtomicU64::new(0);
+static LOANS_OBSERVED: AtomicU64 = AtomicU64::new(0);
+static FEES_PAID: Mutex<BTreeMap<Address, U256>> = Mutex::new(BTreeMap::new());
+static GAS_USED: Histogram = Histogram::new(&GAS_BUCKETS);
+static TX_LATENCY: Histogram = Histogram::new(&LATENCY_BUCKETS);
+
+struct Histogram {
+    bounds: &'static [f64],
+    counts: [AtomicU64; 7],
+    count: AtomicU64,
+    // f64 bits, updated with compare-and-swap
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    const fn new(bounds: &'static [f64; 7]) -> Self {
+        Self {
+            bounds,
+            counts: [
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+            ],
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            if value <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let sum = f64::from_bits(self.sum.load(Ordering::Relaxed));
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// Record a finished flash loan run. Reverted transactions and errors both
+/// count as failures; fees and gas are only recorded for mined loans, and
+/// latency for loans that got a receipt.
+pub fn record_run(result: &Result<FlashLoanOutcome>) {
+    match result {
+        Ok(outcome) if outcome.success => {
+            LOANS_EXECUTED.fetch_add(1, Ordering::Relaxed);
+            let mut fees = FEES_PAID.lock().expect("metrics lock poisoned");
+            for leg in &outcome.legs {
+                *fees.entry(leg.token).or_default() += leg.actual_fee.raw();
+            }
+        }
+        _ => {
+            LOAN_FAILURES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    if let Ok(outcome) = result {
+        GAS_USED.observe(outcome.gas_used.as_u64() as f64);
+        TX_LATENCY.observe(outcome.latency_secs);
+    }
+}
+
+/// Count a flash loan seen by `watch`.
+pub fn record_observed() {
+    LOANS_OBSERVED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Current values in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    let counter = |out: &mut String, name: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+    counter(
+        &mut out,
+        "capacitor_loans_executed_total",
+        "Flash loans that mined successfully.",
+        LOANS_EXECUTED.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "capacitor_loan_failures_total",
+        "Flash loans that reverted or could not be sent.",
+        LOAN_FAILURES.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "capacitor_loans_observed_total",
+        "Flash loan events seen by watch.",
+        LOANS_OBSERVED.load(Ordering::Relaxed),
+    );
+
+    let _ = writeln!(
+        out,
+        "# HELP capacitor_fees_paid_total Fees paid, in token base units."
+    );
+    let _ = writeln!(out, "# TYPE capacitor_fees_paid_total counter");
+    for (token, fee) in FEES_PAID.lock().expect("metrics lock poisoned").iter() {
+        let _ = writeln!(
+            out,
+            "capacitor_fees_paid_total{{token=\"{:?}\"}} {}",
+            token, fee
+        );
+    }
+
+    GAS_USED.render(&mut out, "capacitor_gas_used", "Gas used per flash loan.");
+    TX_LATENCY.render(
+        &mut out,
+        "capacitor_tx_latency_seconds",
+        "Time from sending a flash loan to its receipt.",
+    );
+    out
+}
+
+/// Serve [`render`] over HTTP on `addr` until the process exits.
+///
+/// Every request gets the metrics regardless of path, which is all a
+/// Prometheus scraper needs.
+pub async fn serve(addr: SocketAddr) -> Result<JoinHandle<()>> {
+    let listener = TcpListener::bind(addr).await?;
+    Ok(tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let body = render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{amount::TokenAmount, flash_loan::LoanLeg};
+    use ethers::types::{TransactionReceipt, H256, U64};
+    use std::time::Duration;
+
+    fn outcome(success: bool, fee: u64, latency: Duration) -> FlashLoanOutcome {
+        let receipt = TransactionReceipt {
+            status: Some(U64::from(success as u64)),
+            gas_used: Some(U256::from(150_000)),
+            ..Default::default()
+        };
+        let amount = |raw: u64| TokenAmount::from_raw(U256::from(raw), 6);
+        let leg = LoanLeg {
+            token: Address::repeat_byte(0xcc),
+            amount: amount(1_000_000),
+            expected_fee: amount(fee),
+            actual_fee: amount(fee),
+            balance_before: amount(fee),
+            balance_after: amount(0),
+        };
+        FlashLoanOutcome::new(H256::zero(), receipt, latency, vec![leg])
+    }
+
+    // Value of the first sample line starting with `series`
+    fn sample(scrape: &str, series: &str) -> f64 {
+        scrape
+            .lines()
+            .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+            .map_or(0.0, |value| value.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn a_scrape_reports_recorded_runs() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let server = serve(addr).await.unwrap();
+        let url = format!("http://{}/metrics", addr);
+        let scrape = || async { reqwest::get(&url).await.unwrap().text().await.unwrap() };
+        let fees = format!(
+            "capacitor_fees_paid_total{{token=\"{:?}\"}}",
+            Address::repeat_byte(0xcc)
+        );
+
+        let before = scrape().await;
+        record_run(&Ok(outcome(true, 900, Duration::from_millis(1_500))));
+        record_run(&Ok(outcome(true, 100, Duration::from_millis(500))));
+        record_run(&Ok(outcome(false, 0, Duration::from_secs(3))));
+        record_run(&Err(eyre::eyre!("could not send")));
+        record_observed();
+        let after = scrape().await;
+
+        let delta = |series: &str| sample(&after, series) - sample(&before, series);
+        assert_eq!(delta("capacitor_loans_executed_total"), 2.0);
+        assert_eq!(delta("capacitor_loan_failures_total"), 2.0);
+        assert_eq!(delta("capacitor_loans_observed_total"), 1.0);
+        assert_eq!(delta(&fees), 1_000.0);
+        // Mined loans, reverted or not, have gas and latency; errors don't
+        assert_eq!(delta("capacitor_gas_used_count"), 3.0);
+        assert_eq!(delta("capacitor_tx_latency_seconds_count"), 3.0);
+        assert_eq!(delta("capacitor_tx_latency_seconds_sum"), 5.0);
+        assert_eq!(delta("capacitor_tx_latency_seconds_bucket{le=\"1\"}"), 1.0);
+        assert_eq!(delta("capacitor_tx_latency_seconds_bucket{le=\"2\"}"), 2.0);
+        assert!(after.contains("# TYPE capacitor_tx_latency_seconds histogram"));
+        server.abort();
+    }
+}
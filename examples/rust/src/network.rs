@@ -0,0 +1,61 @@
+//! Networks the examples know how to talk to.
+
+use ethers::types::{Address, H256};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Network {
+    #[default]
+    PlasmaTestnet,
+    PlasmaMainnet,
+}
+
+impl Network {
+    pub fn chain_id(self) -> u64 {
+        match self {
+            Network::PlasmaTestnet => 9746,
+            Network::PlasmaMainnet => 9745,
+        }
+    }
+
+    /// Name used for this network in `capacitor.toml`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Network::PlasmaTestnet => "plasma-testnet",
+            Network::PlasmaMainnet => "plasma-mainnet",
+        }
+    }
+
+    /// Block explorer for this network, without a trailing slash.
+    pub fn explorer_url(self) -> &'static str {
+        match self {
+            Network::PlasmaTestnet => "https://testnet.plasmascan.to",
+            Network::PlasmaMainnet => "https://plasmascan.to",
+        }
+    }
+
+    /// Explorer page for a transaction.
+    pub fn tx_url(self, tx_hash: H256) -> String {
+        format!("{}/tx/{:?}", self.explorer_url(), tx_hash)
+    }
+
+    /// Explorer page for an account or contract.
+    pub fn address_url(self, address: Address) -> String {
+        format!("{}/address/{:?}", self.explorer_url(), address)
+    }
+
+    pub fn is_testnet(self) -> bool {
+        matches!(self, Network::PlasmaTestnet)
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::PlasmaTestnet => write!(f, "Plasma Testnet"),
+            Network::PlasmaMainnet => write!(f, "Plasma Mainnet"),
+        }
+    }
+}
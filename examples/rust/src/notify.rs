@@ -0,0 +1,121 @@
+//! Webhook notifications for automated pipelines.
+//!
+//! When `webhook_url` is configured, the outcome of every flash loan run is
+//! POSTed there as JSON. [`notify_run`] is called for every loan the
+//! library sends, whichever command sent it. Delivery problems are reported
+//! but never fail the run itself.
+
+use crate::{error::CapacitorError, flash_loan::FlashLoanOutcome};
+use eyre::Result;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::warn;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// JSON body sent to the webhook, tagged by `result`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum Notification<'a> {
+    Completed {
+        #[serde(flatten)]
+        outcome: &'a FlashLoanOutcome,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// POST `notification` to `webhook_url`.
+///
+/// A non-2xx response is logged as a warning and treated as delivered;
+/// only failing to reach the endpoint at all is an error.
+pub async fn notify(webhook_url: &str, notification: &Notification<'_>) -> Result<()> {
+    let response = reqwest::Client::new()
+        .post(webhook_url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(notification)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        warn!(status = %response.status(), url = webhook_url, "webhook rejected notification");
+    }
+    Ok(())
+}
+
+/// Report the result of a loan to `webhook_url`, if one is set.
+///
+/// A mined loan that reverted is reported as failed. A webhook that can't
+/// be reached is logged as a warning.
+pub async fn notify_run(webhook_url: Option<&str>, result: &Result<FlashLoanOutcome>) {
+    let Some(url) = webhook_url else {
+        return;
+    };
+    if let Err(e) = notify(url, &Notification::of(result)).await {
+        warn!(url, error = %e, "webhook notification failed");
+    }
+}
+
+impl<'a> Notification<'a> {
+    /// What to send for `result`: a reverted loan counts as failed.
+    pub fn of(result: &'a Result<FlashLoanOutcome>) -> Self {
+        match result {
+            Ok(outcome) if outcome.success => Notification::Completed { outcome },
+            Ok(outcome) => Notification::Failed {
+                error: CapacitorError::Reverted(outcome.tx_hash).to_string(),
+            },
+            Err(e) => Notification::Failed {
+                error: format!("{:#}", e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockNode;
+    use ethers::types::{TransactionReceipt, H256, U64};
+    use eyre::eyre;
+    use serde_json::Value;
+
+    fn outcome(status: u64) -> FlashLoanOutcome {
+        let receipt = TransactionReceipt {
+            status: Some(U64::from(status)),
+            ..Default::default()
+        };
+        FlashLoanOutcome::new(H256::repeat_byte(1), receipt, Duration::ZERO, Vec::new())
+    }
+
+    fn json(result: &Result<FlashLoanOutcome>) -> Value {
+        serde_json::to_value(Notification::of(result)).unwrap()
+    }
+
+    #[test]
+    fn a_mined_loan_is_completed() {
+        assert_eq!(json(&Ok(outcome(1)))["result"], "completed");
+    }
+
+    #[test]
+    fn a_reverted_or_failed_loan_is_failed() {
+        let reverted = json(&Ok(outcome(0)));
+        assert_eq!(reverted["result"], "failed");
+        assert!(reverted["error"].as_str().unwrap().contains("reverted"));
+
+        let failed = json(&Err(eyre!("nonce too low")));
+        assert_eq!(failed["result"], "failed");
+        assert_eq!(failed["error"], "nonce too low");
+    }
+
+    #[tokio::test]
+    async fn runs_are_posted_only_when_a_webhook_is_set() {
+        // The webhook body isn't JSON-RPC, so the mock sees no method
+        let hook = MockNode::start(|_, _| Ok(Value::Null)).await.unwrap();
+        notify_run(None, &Ok(outcome(1))).await;
+        assert!(hook.calls().is_empty());
+
+        notify_run(Some(hook.url()), &Ok(outcome(1))).await;
+        assert_eq!(hook.calls(), [""]);
+    }
+}
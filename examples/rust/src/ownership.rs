@@ -0,0 +1,84 @@
+//! Handing a deployed tester to a new owner.
+//!
+//! Loans, withdrawals and most checks are gated on `owner()`, so after a
+//! transfer only the new owner's key can drive the contract. The transfer
+//! is checked on both sides: the configured owner must hold the contract
+//! beforehand, and `owner()` must report the new owner afterwards.
+//!
+//! The stock FlashLoanTester has no `transferOwnership`; add one (gated
+//! on `onlyOwner`) to a tester you intend to hand off.
+
+use crate::{
+    abi::{IFlashLoanTester, TransferOwnershipCall},
+    client::FlashLoanClient,
+    deployment::bytecode_has_selector,
+    error::CapacitorError,
+    send::TxBuilder,
+};
+use ethers::{contract::EthCall, prelude::*};
+use eyre::Result;
+
+/// Transfer ownership of `tester` from the configured owner to `new_owner`.
+///
+/// Fails with `CapacitorError::WiringMismatch` before sending if the
+/// configured owner doesn't own `tester`, with `VerificationFailed` after
+/// mining if `owner()` doesn't report `new_owner`, and with `Unsupported`
+/// when `tester` has no `transferOwnership`.
+pub async fn transfer_ownership(
+    client: &FlashLoanClient,
+    tester: Address,
+    new_owner: Address,
+) -> Result<TransactionReceipt> {
+    if new_owner.is_zero() {
+        return Err(CapacitorError::InvalidAddress(
+            "the zero address cannot own the tester".to_string(),
+        )
+        .into());
+    }
+
+    if !supports_transfer(client, tester).await? {
+        return Err(CapacitorError::Unsupported(format!(
+            "contract {:?} has no transferOwnership(address)",
+            tester
+        ))
+        .into());
+    }
+
+    let contract = IFlashLoanTester::new(tester, client.owner.clone());
+    let current = contract.owner().await?;
+    if current != client.owner_address() {
+        return Err(CapacitorError::WiringMismatch {
+            what: "contract owner",
+            expected: client.owner_address(),
+            actual: current,
+        }
+        .into());
+    }
+
+    let call = contract.transfer_ownership(new_owner);
+    let receipt = TxBuilder::call(client, &client.owner, call)
+        .send_and_confirm()
+        .await?;
+    if receipt.status != Some(U64::from(1)) {
+        return Err(CapacitorError::Reverted(receipt.transaction_hash).into());
+    }
+
+    let owner = contract.owner().await?;
+    if owner != new_owner {
+        return Err(CapacitorError::VerificationFailed {
+            what: "contract owner",
+            expected: new_owner,
+            actual: owner,
+        }
+        .into());
+    }
+    Ok(receipt)
+}
+
+async fn supports_transfer(client: &FlashLoanClient, tester: Address) -> Result<bool> {
+    let code = client.provider.get_code(tester, None).await?;
+    Ok(bytecode_has_selector(
+        &code,
+        TransferOwnershipCall::selector(),
+    ))
+}
@@ -0,0 +1,60 @@
+//! Writing each run to disk for record-keeping.
+//!
+//! With `--save`, every flash loan leaves a JSON file in `output_dir`
+//! holding the outcome, the decoded events and the full receipt. The log
+//! then doesn't depend on an explorer being up, or still indexing the
+//! chain, when the run is audited later.
+
+use crate::{amount::TokenAmount, flash_loan::FlashLoanOutcome, network::Network};
+use ethers::types::Address;
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Contents of a saved run.
+#[derive(Debug, Serialize)]
+pub struct RunRecord<'a> {
+    /// Seconds since the Unix epoch when the record was written.
+    pub timestamp: u64,
+    pub network: Network,
+    pub chain_id: u64,
+    /// Borrowed token and amount, for a single-token loan. A batch lists
+    /// them per leg.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<TokenAmount>,
+    #[serde(flatten)]
+    pub outcome: &'a FlashLoanOutcome,
+}
+
+/// Write `outcome` to a new timestamped file in `dir`, creating `dir` if
+/// needed, and return the file's path.
+pub fn persist_run(network: Network, outcome: &FlashLoanOutcome, dir: &Path) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let single = match outcome.legs.as_slice() {
+        [leg] => Some(leg),
+        _ => None,
+    };
+    let record = RunRecord {
+        timestamp,
+        network,
+        chain_id: network.chain_id(),
+        token: single.map(|leg| leg.token),
+        amount: single.map(|leg| leg.amount),
+        outcome,
+    };
+
+    fs::create_dir_all(dir).wrap_err_with(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!("run-{}-{:x}.json", timestamp, outcome.tx_hash));
+    let json = serde_json::to_string_pretty(&record)?;
+    fs::write(&path, json).wrap_err_with(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
@@ -0,0 +1,439 @@
+//! Waiting for transactions to be mined.
+//!
+//! Over HTTP a receipt is found by polling. The interval is set on the
+//! provider so every pending transaction uses it; the wait as a whole is
+//! bounded so a transaction that never lands fails the run instead of
+//! hanging it.
+//!
+//! A missing receipt alone doesn't say whether a transaction was dropped
+//! or is just slow. `await_with_diagnostics` also watches the mempool, and
+//! tells the two apart. Sends wait through it via `confirm_receipt`.
+
+use crate::error::CapacitorError;
+use ethers::{
+    providers::{JsonRpcClient, Middleware, PendingTransaction, Provider},
+    types::{TransactionReceipt, H256},
+};
+use eyre::{eyre, Result};
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// ethers' own interval for HTTP providers.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(7);
+pub const DEFAULT_MAX_POLL_DURATION: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollPolicy {
+    /// Time between receipt lookups.
+    pub interval: Duration,
+    /// Give up waiting for a receipt after this long.
+    pub max_duration: Duration,
+    /// Blocks, counting the one it is mined in, before a transaction counts
+    /// as confirmed.
+    pub confirmations: usize,
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_POLL_INTERVAL,
+            max_duration: DEFAULT_MAX_POLL_DURATION,
+            confirmations: 1,
+        }
+    }
+}
+
+/// Wait for `pending` to be mined, for at most `policy.max_duration`.
+///
+/// `None` means the transaction was dropped from the mempool. Running out
+/// of time fails with `CapacitorError::Timeout`; the transaction may still
+/// mine later, so look it up by hash before resending.
+pub async fn wait_for_receipt<P: JsonRpcClient>(
+    pending: PendingTransaction<'_, P>,
+    policy: PollPolicy,
+) -> Result<Option<TransactionReceipt>> {
+    let tx_hash = pending.tx_hash();
+    let pending = pending
+        .interval(policy.interval)
+        .confirmations(policy.confirmations);
+    match tokio::time::timeout(policy.max_duration, pending).await {
+        Ok(receipt) => Ok(receipt?),
+        Err(_) => Err(CapacitorError::Timeout {
+            tx_hash,
+            waited: policy.max_duration,
+        }
+        .into()),
+    }
+}
+
+/// What became of a transaction, as seen from the node.
+#[derive(Debug, Clone)]
+pub enum TxStatus {
+    /// Mined with at least the confirmations asked for.
+    Mined(Box<TransactionReceipt>),
+    /// Mined, but with fewer confirmations than asked for when the wait
+    /// ran out.
+    Unconfirmed(Box<TransactionReceipt>),
+    /// Seen in the mempool, then gone without being mined.
+    Dropped,
+    /// Still in the mempool when the wait ran out.
+    Pending,
+    /// Never seen by the node; it may not have been broadcast.
+    NotFound,
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxStatus::Mined(receipt) => match receipt.block_number {
+                Some(block) => write!(f, "mined in block {}", block),
+                None => write!(f, "mined"),
+            },
+            TxStatus::Unconfirmed(receipt) => match receipt.block_number {
+                Some(block) => write!(f, "mined in block {}, not yet confirmed", block),
+                None => write!(f, "mined, not yet confirmed"),
+            },
+            TxStatus::Dropped => write!(f, "dropped from mempool"),
+            TxStatus::Pending => write!(f, "pending in mempool"),
+            TxStatus::NotFound => write!(f, "not found by the node"),
+        }
+    }
+}
+
+/// Wait up to `policy.max_duration` for `tx_hash`, checking the receipt
+/// and the mempool every `policy.interval`.
+///
+/// Returns as soon as the transaction has `policy.confirmations`, or is
+/// dropped after having been seen. Otherwise reports `Unconfirmed`,
+/// `Pending` or `NotFound` once time runs out, even if a lookup to the node
+/// is still outstanding.
+pub async fn await_with_diagnostics<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx_hash: H256,
+    policy: PollPolicy,
+) -> Result<TxStatus> {
+    let mut seen = Seen::default();
+    let polled = tokio::time::timeout(
+        policy.max_duration,
+        poll_status(provider, tx_hash, policy, &mut seen),
+    )
+    .await;
+    match polled {
+        Ok(Some(status)) => status,
+        // Out of time, between lookups or in the middle of one
+        Ok(None) | Err(_) => Ok(match seen.receipt {
+            Some(receipt) => TxStatus::Unconfirmed(Box::new(receipt)),
+            None if seen.in_mempool => TxStatus::Pending,
+            None => TxStatus::NotFound,
+        }),
+    }
+}
+
+// What the lookups so far have found, kept outside the polling future so it
+// survives the future being cut off by the timeout
+#[derive(Default)]
+struct Seen {
+    in_mempool: bool,
+    receipt: Option<TransactionReceipt>,
+}
+
+// Poll until the transaction is confirmed or dropped, or `None` once the
+// deadline passes between lookups
+async fn poll_status<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx_hash: H256,
+    policy: PollPolicy,
+    seen: &mut Seen,
+) -> Option<Result<TxStatus>> {
+    let deadline = Instant::now() + policy.max_duration;
+    loop {
+        let receipt = match provider.get_transaction_receipt(tx_hash).await {
+            Ok(receipt) => receipt,
+            Err(e) => return Some(Err(e.into())),
+        };
+        match &receipt {
+            Some(receipt) => match confirmations(provider, receipt).await {
+                Ok(confirmed) if confirmed >= policy.confirmations => {
+                    return Some(Ok(TxStatus::Mined(Box::new(receipt.clone()))));
+                }
+                Ok(_) => seen.receipt = Some(receipt.clone()),
+                Err(e) => return Some(Err(e)),
+            },
+            None => match provider.get_transaction(tx_hash).await {
+                Ok(Some(_)) => seen.in_mempool = true,
+                Ok(None) if seen.in_mempool => {
+                    // It may have been mined between the two lookups
+                    match provider.get_transaction_receipt(tx_hash).await {
+                        Ok(Some(_)) => continue,
+                        Ok(None) => return Some(Ok(TxStatus::Dropped)),
+                        Err(e) => return Some(Err(e.into())),
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => return Some(Err(e.into())),
+            },
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        tokio::time::sleep(policy.interval.min(deadline - now)).await;
+    }
+}
+
+/// Wait for `tx_hash` as [`await_with_diagnostics`] does, and return its
+/// receipt once confirmed.
+///
+/// A reverted transaction still returns its receipt; check `status`.
+/// Running out of time fails with `CapacitorError::Timeout`, whether the
+/// transaction was still pending, unconfirmed or not yet seen by the node.
+/// A transaction dropped from the mempool fails right away.
+pub async fn confirm_receipt<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx_hash: H256,
+    policy: PollPolicy,
+) -> Result<TransactionReceipt> {
+    match await_with_diagnostics(provider, tx_hash, policy).await? {
+        TxStatus::Mined(receipt) => Ok(*receipt),
+        TxStatus::Dropped => Err(eyre!("transaction {:?} was dropped from mempool", tx_hash)),
+        TxStatus::Unconfirmed(_) | TxStatus::Pending | TxStatus::NotFound => {
+            Err(CapacitorError::Timeout {
+                tx_hash,
+                waited: policy.max_duration,
+            }
+            .into())
+        }
+    }
+}
+
+// Blocks from the one `receipt` was mined in to the latest, inclusive
+async fn confirmations<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    receipt: &TransactionReceipt,
+) -> Result<usize> {
+    let Some(mined) = receipt.block_number else {
+        return Ok(0);
+    };
+    let latest = provider.get_block_number().await?;
+    Ok(latest.saturating_sub(mined).as_usize() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::MockNode, Config};
+    use ethers::{
+        providers::Http,
+        types::{Transaction, TransactionReceipt, U64},
+    };
+    use serde_json::Value;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    fn policy(max_duration: Duration) -> PollPolicy {
+        PollPolicy {
+            interval: Duration::from_millis(10),
+            max_duration,
+            confirmations: 1,
+        }
+    }
+
+    fn pending(tx_hash: H256) -> Value {
+        serde_json::to_value(Transaction {
+            hash: tx_hash,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn is_timeout(err: &eyre::Report) -> bool {
+        matches!(
+            err.downcast_ref::<CapacitorError>(),
+            Some(CapacitorError::Timeout { .. })
+        )
+    }
+
+    fn mined(tx_hash: H256) -> Value {
+        serde_json::to_value(TransactionReceipt {
+            transaction_hash: tx_hash,
+            block_number: Some(U64::from(7)),
+            status: Some(U64::from(1)),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_short_max_duration_times_out_against_a_slow_node() {
+        // Every lookup takes longer than the whole wait is allowed to
+        let node = MockNode::start_with_latency(Duration::from_millis(300), |_, _| Ok(Value::Null))
+            .await
+            .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+        let tx_hash = H256::repeat_byte(0x11);
+        let config: Config = toml::from_str("max_poll_duration = 0.05").unwrap();
+
+        let err = wait_for_receipt(
+            PendingTransaction::new(tx_hash, &provider),
+            config.poll_policy(),
+        )
+        .await
+        .unwrap_err();
+
+        match err.downcast_ref::<CapacitorError>() {
+            Some(CapacitorError::Timeout {
+                tx_hash: hash,
+                waited,
+            }) => {
+                assert_eq!(*hash, tx_hash);
+                assert_eq!(*waited, Duration::from_millis(50));
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert_eq!(crate::error::exit_code(&err), 5);
+    }
+
+    #[tokio::test]
+    async fn a_receipt_within_max_duration_is_returned() {
+        let tx_hash = H256::repeat_byte(0x11);
+        let node = MockNode::start(move |method, _| match method {
+            "eth_getTransactionByHash" => Ok(serde_json::to_value(Transaction {
+                hash: tx_hash,
+                block_number: Some(U64::from(7)),
+                ..Default::default()
+            })
+            .unwrap()),
+            "eth_getTransactionReceipt" => Ok(mined(tx_hash)),
+            "eth_blockNumber" => Ok(Value::from("0x7")),
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+
+        let receipt = wait_for_receipt(
+            PendingTransaction::new(tx_hash, &provider),
+            policy(Duration::from_secs(5)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(receipt.transaction_hash, tx_hash);
+    }
+
+    #[tokio::test]
+    async fn confirming_times_out_against_a_slow_node() {
+        let node = MockNode::start_with_latency(Duration::from_millis(300), |_, _| Ok(Value::Null))
+            .await
+            .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+
+        let err = confirm_receipt(
+            &provider,
+            H256::repeat_byte(0x11),
+            policy(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap_err();
+        assert!(is_timeout(&err), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn a_stalled_lookup_does_not_outlast_max_duration() {
+        // The first receipt lookup alone outlasts the wait many times over
+        let node = MockNode::start_with_latency(Duration::from_secs(30), |_, _| Ok(Value::Null))
+            .await
+            .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+        let tx_hash = H256::repeat_byte(0x11);
+
+        let started = Instant::now();
+        let err = confirm_receipt(&provider, tx_hash, policy(Duration::from_millis(50)))
+            .await
+            .unwrap_err();
+        assert!(is_timeout(&err), "{:?}", err);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        // Cut off inside the first lookup, before the mempool was checked
+        assert_eq!(node.count("eth_getTransactionByHash"), 0);
+    }
+
+    #[tokio::test]
+    async fn confirming_waits_for_the_policy_confirmations() {
+        let tx_hash = H256::repeat_byte(0x11);
+        // Mined in block 7, and each block number lookup finds a new block
+        let head = Arc::new(AtomicU64::new(7));
+        let node = MockNode::start(move |method, _| match method {
+            "eth_getTransactionReceipt" => Ok(mined(tx_hash)),
+            "eth_blockNumber" => Ok(Value::from(format!(
+                "{:#x}",
+                head.fetch_add(1, Ordering::SeqCst)
+            ))),
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+        let policy = PollPolicy {
+            confirmations: 3,
+            ..policy(Duration::from_secs(5))
+        };
+
+        let receipt = confirm_receipt(&provider, tx_hash, policy).await.unwrap();
+        assert_eq!(receipt.transaction_hash, tx_hash);
+        // Blocks 7 and 8 were too early; block 9 gives three confirmations
+        assert_eq!(node.count("eth_blockNumber"), 3);
+    }
+
+    #[tokio::test]
+    async fn a_mined_but_unconfirmed_transaction_times_out() {
+        let tx_hash = H256::repeat_byte(0x11);
+        let node = MockNode::start(move |method, _| match method {
+            "eth_getTransactionReceipt" => Ok(mined(tx_hash)),
+            "eth_blockNumber" => Ok(Value::from("0x7")),
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+        let policy = PollPolicy {
+            confirmations: 2,
+            ..policy(Duration::from_millis(100))
+        };
+
+        let status = await_with_diagnostics(&provider, tx_hash, policy)
+            .await
+            .unwrap();
+        assert!(matches!(status, TxStatus::Unconfirmed(_)), "{}", status);
+        let err = confirm_receipt(&provider, tx_hash, policy)
+            .await
+            .unwrap_err();
+        assert!(is_timeout(&err), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn a_dropped_transaction_fails_without_waiting_out_the_timeout() {
+        let tx_hash = H256::repeat_byte(0x11);
+        // In the mempool for the first lookup, gone after
+        let lookups = Arc::new(AtomicU64::new(0));
+        let node = MockNode::start(move |method, _| match method {
+            "eth_getTransactionByHash" if lookups.fetch_add(1, Ordering::SeqCst) == 0 => {
+                Ok(pending(tx_hash))
+            }
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap();
+        let provider = Provider::<Http>::try_from(node.url()).unwrap();
+
+        let err = confirm_receipt(&provider, tx_hash, policy(Duration::from_secs(30)))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().ends_with("dropped from mempool"), "{}", err);
+        assert!(!is_timeout(&err));
+    }
+}
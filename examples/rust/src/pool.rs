@@ -0,0 +1,85 @@
+//! The flash loan provider's token reserves.
+
+use crate::{
+    abi::{IFlashLoanProvider, SupportedTokensCall, IERC20},
+    deployment::bytecode_has_selector,
+    error::CapacitorError,
+    fee::BPS_DENOMINATOR,
+};
+use ethers::{contract::EthCall, providers::Middleware, types::Address, types::U256};
+use eyre::Result;
+use std::sync::Arc;
+
+/// Tokens the provider holds and can lend right now.
+///
+/// This is the provider's own `balanceOf` for `token`. It can be lower
+/// than any configured per-loan cap, so check both.
+pub async fn pool_liquidity<M: Middleware + 'static>(
+    client: Arc<M>,
+    provider_addr: Address,
+    token: Address,
+) -> Result<U256> {
+    Ok(IERC20::new(token, client).balance_of(provider_addr).await?)
+}
+
+/// Tokens the provider lends, from its `supportedTokens()` list.
+///
+/// Providers are not required to enumerate their tokens. When the code at
+/// `provider_addr` has no `supportedTokens` entry point this fails with
+/// `CapacitorError::Unsupported`; look the token up by address instead.
+pub async fn supported_tokens<M: Middleware + 'static>(
+    client: Arc<M>,
+    provider_addr: Address,
+) -> Result<Vec<Address>> {
+    let code = client.get_code(provider_addr, None).await?;
+    if !bytecode_has_selector(&code, SupportedTokensCall::selector()) {
+        return Err(CapacitorError::Unsupported(format!(
+            "provider {:?} does not list its tokens (no supportedTokens())",
+            provider_addr
+        ))
+        .into());
+    }
+    Ok(IFlashLoanProvider::new(provider_addr, client)
+        .supported_tokens()
+        .await?)
+}
+
+/// Check that the provider's reserve grew by exactly the fee across a loan.
+///
+/// `before_reserve` is the pool liquidity read before the loan was sent. A
+/// delta further than `tolerance_bps` of `expected_fee` from it means the
+/// borrower over- or under-repaid. Other loans landing in between also move
+/// the reserve, so run this where you are the only borrower.
+pub async fn assert_repayment<M: Middleware + 'static>(
+    client: Arc<M>,
+    provider_addr: Address,
+    token: Address,
+    before_reserve: U256,
+    expected_fee: U256,
+    tolerance_bps: u32,
+) -> Result<()> {
+    let after_reserve = pool_liquidity(client, provider_addr, token).await?;
+    check_repayment(before_reserve, after_reserve, expected_fee, tolerance_bps)?;
+    Ok(())
+}
+
+/// Fail unless `after - before` is within `tolerance_bps` of `expected_fee`.
+pub fn check_repayment(
+    before_reserve: U256,
+    after_reserve: U256,
+    expected_fee: U256,
+    tolerance_bps: u32,
+) -> Result<(), CapacitorError> {
+    let slack = expected_fee * U256::from(tolerance_bps) / U256::from(BPS_DENOMINATOR);
+    let low = before_reserve + expected_fee.saturating_sub(slack);
+    let high = before_reserve + expected_fee + slack;
+
+    if after_reserve < low || after_reserve > high {
+        return Err(CapacitorError::RepaymentMismatch {
+            expected_fee,
+            before: before_reserve,
+            after: after_reserve,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Checks run before any transaction is sent.
+//!
+//! Catching a doomed flash loan here costs one `eth_call` instead of the
+//! gas of a reverted transaction.
+
+use crate::{
+    abi::IFlashLoanTester, client::FlashLoanClient, error::CapacitorError, expect::Expect,
+    flash_loan::FlashLoanParams, pool::supported_tokens,
+};
+use ethers::{
+    providers::Middleware,
+    types::{Address, U256},
+};
+use eyre::Result;
+
+/// What preflight learned about the pool.
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    pub liquidity: U256,
+}
+
+/// Verify `params` can be served by the provider at `provider_addr`.
+///
+/// An empty reserve fails with `CapacitorError::NoPool` when the provider
+/// doesn't list the token, and with `PoolInsufficientLiquidity` otherwise.
+pub async fn preflight(
+    client: &FlashLoanClient,
+    provider_addr: Address,
+    params: &FlashLoanParams,
+) -> Result<Preflight> {
+    let requested = params.amount.raw();
+    let checked = Expect::balance(params.token, provider_addr)
+        .at_least(requested)
+        .or(move |available| CapacitorError::PoolInsufficientLiquidity {
+            requested,
+            available,
+        })
+        .check(client.owner.clone())
+        .await;
+    match checked {
+        Ok(liquidity) => Ok(Preflight { liquidity }),
+        Err(e) if is_empty_pool(&e) => {
+            match check_pool(client, provider_addr, params.token).await {
+                Ok(()) => Err(e),
+                Err(no_pool) => Err(no_pool.into()),
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Fail with `CapacitorError::NoPool` unless `provider_addr` lists
+/// `token` among its supported tokens.
+///
+/// A provider that doesn't list its tokens is taken to have no pool, with
+/// the hint saying the list is unavailable.
+pub async fn check_pool(
+    client: &FlashLoanClient,
+    provider_addr: Address,
+    token: Address,
+) -> Result<(), CapacitorError> {
+    let supported = match supported_tokens(client.owner.clone(), provider_addr).await {
+        Ok(tokens) if tokens.contains(&token) => return Ok(()),
+        Ok(tokens) if tokens.is_empty() => "none".to_string(),
+        Ok(tokens) => tokens
+            .iter()
+            .map(|token| format!("{:?}", token))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Err(_) => "unknown, the provider does not list its tokens".to_string(),
+    };
+    Err(CapacitorError::NoPool { token, supported })
+}
+
+// An empty reserve usually means a token the provider doesn't lend
+fn is_empty_pool(error: &eyre::Report) -> bool {
+    matches!(
+        error.downcast_ref(),
+        Some(CapacitorError::PoolInsufficientLiquidity { available, .. }) if available.is_zero()
+    )
+}
+
+/// Check that `provider_addr` is a contract and compare it with the
+/// provider `tester` was deployed against.
+///
+/// The tester always borrows from its own provider, so when the two differ
+/// the checks here describe a different pool than the loan will use.
+/// Returns the tester's provider in that case.
+pub async fn check_provider(
+    client: &FlashLoanClient,
+    tester: Address,
+    provider_addr: Address,
+) -> Result<Option<Address>> {
+    if client
+        .provider
+        .get_code(provider_addr, None)
+        .await?
+        .is_empty()
+    {
+        return Err(CapacitorError::NoContractCode(provider_addr).into());
+    }
+    let wired = IFlashLoanTester::new(tester, client.owner.clone())
+        .flash_loan_provider()
+        .await?;
+    Ok((wired != provider_addr).then_some(wired))
+}
+
+/// Fail if `requested` is over the configured `max_loan_amount`.
+pub fn check_loan_cap(requested: U256, cap: Option<U256>) -> Result<(), CapacitorError> {
+    match cap {
+        Some(cap) if requested > cap => Err(CapacitorError::LoanCapExceeded { requested, cap }),
+        _ => Ok(()),
+    }
+}
+
+/// Verify the tester already holds enough of `params.token` to pay
+/// `params.expected_fee`.
+///
+/// Run this after funding: a fee-on-transfer or otherwise non-standard
+/// token can deliver less than was sent, and the loan would then revert
+/// on repayment.
+pub async fn check_funded(client: &FlashLoanClient, params: &FlashLoanParams) -> Result<U256> {
+    let need = params.expected_fee.raw();
+    Expect::balance(params.token, params.tester)
+        .at_least(need)
+        .or(move |have| CapacitorError::Underfunded { have, need })
+        .check(client.owner.clone())
+        .await
+}
+
+/// Verify `account` holds enough native token to pay `cost` wei of gas.
+///
+/// A configured `min_native_balance` replaces `cost` as the requirement.
+/// Returns the account's balance.
+pub async fn check_gas_funds(
+    client: &FlashLoanClient,
+    account: Address,
+    cost: U256,
+) -> Result<U256> {
+    let have = client.provider.get_balance(account, None).await?;
+    check_gas(have, client.min_native_balance.unwrap_or(cost))?;
+    Ok(have)
+}
+
+/// Fail if `have` does not cover the gas cost `need`.
+pub fn check_gas(have: U256, need: U256) -> Result<(), CapacitorError> {
+    if have < need {
+        return Err(CapacitorError::InsufficientGas { have, need });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn loans_up_to_the_cap_pass() {
+        let cap = Some(U256::from(100));
+        assert!(check_loan_cap(U256::from(99), cap).is_ok());
+        assert!(check_loan_cap(U256::from(100), cap).is_ok());
+        // No cap configured, no limit
+        assert!(check_loan_cap(U256::MAX, None).is_ok());
+    }
+
+    #[test]
+    fn a_loan_over_the_cap_fails_preflight() {
+        let err = check_loan_cap(U256::from(101), Some(U256::from(100))).unwrap_err();
+        assert!(matches!(
+            err,
+            CapacitorError::LoanCapExceeded { requested, cap }
+                if requested == U256::from(101) && cap == U256::from(100)
+        ));
+        assert_eq!(err.exit_code(), 3);
+    }
+
+    #[test]
+    fn the_cap_is_scaled_by_the_token_decimals() {
+        let config: Config = toml::from_str("max_loan_amount = 2.5").unwrap();
+        let cap = config.max_loan_amount(6).unwrap();
+        assert_eq!(cap, Some(U256::from(2_500_000)));
+        assert!(check_loan_cap(U256::from(2_500_001), cap).is_err());
+        assert_eq!(Config::default().max_loan_amount(6).unwrap(), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Back-of-envelope profitability of a round trip, with no RPC.
+//!
+//! The model: borrow `loan_amount` of the loan token, buy an asset with it
+//! at `buy_price`, sell the asset back at `sell_price`, repay the loan plus
+//! fee. Prices are in loan-token units per unit of the asset; gas is given
+//! in loan-token units too, so every figure shares one unit. Slippage and
+//! price impact are not modelled, so treat a thin margin as a loss.
+
+use crate::{error::CapacitorError, fee::BPS_DENOMINATOR};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfitEstimate {
+    /// What the buy-then-sell gains before any costs.
+    pub gross_spread: f64,
+    pub flash_loan_fee: f64,
+    pub gas_cost: f64,
+    /// `gross_spread - flash_loan_fee - gas_cost`.
+    pub net_profit: f64,
+    pub profitable: bool,
+}
+
+/// Estimate the round trip described in the module docs.
+///
+/// Fails with `CapacitorError::Config` unless every figure is finite and
+/// `buy_price` is positive.
+pub fn estimate_profit(
+    loan_amount: f64,
+    buy_price: f64,
+    sell_price: f64,
+    fee_bps: u32,
+    gas_cost: f64,
+) -> Result<ProfitEstimate, CapacitorError> {
+    for (name, value) in [
+        ("loan amount", loan_amount),
+        ("buy price", buy_price),
+        ("sell price", sell_price),
+        ("gas cost", gas_cost),
+    ] {
+        if !value.is_finite() {
+            return Err(CapacitorError::Config(format!(
+                "{} must be a finite number, got {}",
+                name, value
+            )));
+        }
+    }
+    if buy_price <= 0.0 {
+        return Err(CapacitorError::Config(format!(
+            "buy price must be positive, got {}",
+            buy_price
+        )));
+    }
+
+    let asset = loan_amount / buy_price;
+    let gross_spread = asset * sell_price - loan_amount;
+    let flash_loan_fee = loan_amount * f64::from(fee_bps) / BPS_DENOMINATOR as f64;
+    let net_profit = gross_spread - flash_loan_fee - gas_cost;
+    Ok(ProfitEstimate {
+        gross_spread,
+        flash_loan_fee,
+        gas_cost,
+        net_profit,
+        profitable: net_profit > 0.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread_above_costs_is_profitable() {
+        let estimate = estimate_profit(10_000.0, 2.0, 2.5, 1, 100.0).unwrap();
+        assert_eq!(estimate.gross_spread, 2_500.0);
+        assert_eq!(estimate.flash_loan_fee, 1.0);
+        assert_eq!(estimate.net_profit, 2_399.0);
+        assert!(estimate.profitable);
+    }
+
+    #[test]
+    fn break_even_is_not_profitable() {
+        // 2,500 of spread against 1 of fee and 2,499 of gas
+        let estimate = estimate_profit(10_000.0, 2.0, 2.5, 1, 2_499.0).unwrap();
+        assert_eq!(estimate.net_profit, 0.0);
+        assert!(!estimate.profitable);
+    }
+
+    #[test]
+    fn costs_above_the_spread_lose_money() {
+        // The fee alone eats a spread smaller than it
+        let estimate = estimate_profit(10_000.0, 2.0, 2.0, 5, 0.0).unwrap();
+        assert_eq!(estimate.gross_spread, 0.0);
+        assert_eq!(estimate.net_profit, -5.0);
+        assert!(!estimate.profitable);
+
+        // Selling below the buy price loses before any costs
+        let estimate = estimate_profit(10_000.0, 2.5, 2.0, 1, 10.0).unwrap();
+        assert_eq!(estimate.gross_spread, -2_000.0);
+        assert_eq!(estimate.net_profit, -2_011.0);
+        assert!(!estimate.profitable);
+    }
+
+    #[test]
+    fn unusable_prices_are_config_errors() {
+        for (buy, sell) in [
+            (0.0, 2.0),
+            (-1.0, 2.0),
+            (f64::NAN, 2.0),
+            (2.0, f64::INFINITY),
+        ] {
+            let err = estimate_profit(10_000.0, buy, sell, 1, 0.0).unwrap_err();
+            assert!(matches!(err, CapacitorError::Config(_)), "{}", err);
+            assert_eq!(err.exit_code(), 2);
+        }
+    }
+}
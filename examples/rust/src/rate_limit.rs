@@ -0,0 +1,145 @@
+//! Token-bucket rate limiting for outgoing JSON-RPC requests.
+//!
+//! Public RPC endpoints throttle bursty clients. `RateLimiter` wraps a
+//! transport so every request waits for a token before it is sent. The
+//! bucket holds one second worth of requests and refills continuously;
+//! callers queue in order while it is empty. A limit of `0` disables it.
+
+use async_trait::async_trait;
+use ethers::providers::JsonRpcClient;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt::Debug, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
+
+/// Clones share the same bucket, so every signer built on one provider is
+/// counted against a single limit.
+#[derive(Debug, Clone)]
+pub struct RateLimiter<T> {
+    inner: T,
+    bucket: Option<Arc<Mutex<Bucket>>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    last_refill: Instant,
+}
+
+impl<T> RateLimiter<T> {
+    /// Wrap `inner`, allowing at most `requests_per_second` requests per second.
+    pub fn new(inner: T, requests_per_second: u32) -> Self {
+        let bucket = (requests_per_second > 0).then(|| {
+            let per_second = f64::from(requests_per_second);
+            Arc::new(Mutex::new(Bucket {
+                capacity: per_second,
+                tokens: per_second,
+                per_second,
+                last_refill: Instant::now(),
+            }))
+        });
+        Self { inner, bucket }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Wait until a request may be sent.
+    pub async fn acquire(&self) {
+        let Some(bucket) = &self.bucket else {
+            return;
+        };
+
+        // The lock is held while sleeping so waiting callers are served in order.
+        let mut bucket = bucket.lock().await;
+        bucket.refill();
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) / bucket.per_second;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+            bucket.refill();
+        }
+        bucket.tokens = (bucket.tokens - 1.0).max(0.0);
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.last_refill = now;
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient> JsonRpcClient for RateLimiter<T> {
+    type Error = T::Error;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        self.acquire().await;
+        self.inner.request(method, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn a_full_bucket_allows_one_second_of_requests_at_once() {
+        let limiter = RateLimiter::new((), 5);
+        let started = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire().await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        limiter.acquire().await;
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rapid_requests_are_spread_at_the_configured_rate() {
+        let limiter = RateLimiter::new((), 10);
+        let started = Instant::now();
+        for _ in 0..30 {
+            limiter.acquire().await;
+        }
+        // 10 from the initial burst, then 20 more at 10 per second
+        assert!(started.elapsed() >= Duration::from_secs(2));
+        assert!(started.elapsed() < Duration::from_millis(2100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn an_idle_bucket_refills_up_to_capacity() {
+        let limiter = RateLimiter::new((), 4);
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        tokio::time::sleep(Duration::from_secs(10)).await;
+
+        let started = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        limiter.acquire().await;
+        assert_eq!(started.elapsed(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_disables_the_limit() {
+        let limiter = RateLimiter::new((), 0);
+        let started = Instant::now();
+        for _ in 0..1000 {
+            limiter.acquire().await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+}
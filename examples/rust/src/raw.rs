@@ -0,0 +1,80 @@
+//! Calling arbitrary functions from a human-readable signature.
+//!
+//! Lets a modified FlashLoanTester be exercised without regenerating the
+//! bindings: `"function setTarget(address)"`, `"balanceOf(address)(uint256)"`
+//! and `"function owner() view returns (address)"` are all accepted.
+
+use crate::{client::FlashLoanClient, send::TxBuilder};
+use ethers::{
+    abi::{
+        token::{LenientTokenizer, Tokenizer},
+        AbiParser, Function, Token,
+    },
+    prelude::*,
+    types::transaction::eip2718::TypedTransaction,
+};
+use eyre::{eyre, Result, WrapErr};
+
+/// Parse a function signature such as `"balanceOf(address)(uint256)"`.
+pub fn parse_function(signature: &str) -> Result<Function> {
+    AbiParser::default()
+        .parse_function(signature)
+        .map_err(|e| eyre!("invalid function signature {:?}: {}", signature, e))
+}
+
+/// Turn command-line strings into tokens for `function`'s inputs.
+pub fn parse_args(function: &Function, args: &[String]) -> Result<Vec<Token>> {
+    if args.len() != function.inputs.len() {
+        return Err(eyre!(
+            "{} takes {} argument(s), got {}",
+            function.name,
+            function.inputs.len(),
+            args.len()
+        ));
+    }
+    function
+        .inputs
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            LenientTokenizer::tokenize(&param.kind, arg)
+                .wrap_err_with(|| format!("{} is not a valid {}", arg, param.kind))
+        })
+        .collect()
+}
+
+/// `eth_call` `signature` on `contract` and decode the return values.
+pub async fn call_raw<M: Middleware>(
+    client: &M,
+    contract: Address,
+    signature: &str,
+    args: Vec<Token>,
+) -> Result<Vec<Token>>
+where
+    M::Error: 'static,
+{
+    let function = parse_function(signature)?;
+    let tx = call_tx(&function, contract, &args)?;
+    let output = client.call(&tx, None).await?;
+    Ok(function.decode_output(&output)?)
+}
+
+/// Send `signature` to `contract` from the owner and wait for the receipt.
+pub async fn send_raw(
+    client: &FlashLoanClient,
+    contract: Address,
+    signature: &str,
+    args: Vec<Token>,
+) -> Result<TransactionReceipt> {
+    let function = parse_function(signature)?;
+    let mut tx = call_tx(&function, contract, &args)?;
+    tx.set_from(client.owner_address());
+    TxBuilder::new(client, &client.owner, tx)
+        .send_and_confirm()
+        .await
+}
+
+fn call_tx(function: &Function, contract: Address, args: &[Token]) -> Result<TypedTransaction> {
+    let data = function.encode_input(args)?;
+    Ok(TransactionRequest::new().to(contract).data(data).into())
+}
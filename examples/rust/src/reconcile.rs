@@ -0,0 +1,63 @@
+//! Comparing what the tester holds with what its next loan needs.
+//!
+//! Every run leaves the funding that was not spent on fees in the
+//! contract. `reconcile` works out how much of that could be withdrawn
+//! while keeping enough for the next loan's fee.
+
+use crate::{
+    abi::{IFlashLoanTester, IERC20},
+    client::FlashLoanClient,
+    send::TxBuilder,
+};
+use ethers::prelude::*;
+use eyre::Result;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub token: Address,
+    /// The contract's current balance of `token`.
+    pub balance: U256,
+    /// Fee for the next loan, which has to stay in the contract.
+    pub min_fee: U256,
+    /// What can be withdrawn without underfunding the next loan.
+    pub surplus: U256,
+}
+
+/// Report `contract`'s balance of `token` against `next_fee`, the
+/// provider's fee for the next loan.
+pub async fn reconcile<M: Middleware + 'static>(
+    client: Arc<M>,
+    contract: Address,
+    token: Address,
+    next_fee: U256,
+) -> Result<ReconcileReport> {
+    let balance = IERC20::new(token, client).balance_of(contract).await?;
+    let min_fee = next_fee;
+    Ok(ReconcileReport {
+        token,
+        balance,
+        min_fee,
+        surplus: balance.saturating_sub(min_fee),
+    })
+}
+
+/// Withdraw `report.surplus` from `contract` to the owner.
+///
+/// Returns `None` when there is nothing to withdraw.
+pub async fn sweep(
+    client: &FlashLoanClient,
+    contract: Address,
+    report: &ReconcileReport,
+) -> Result<Option<TransactionReceipt>> {
+    if report.surplus.is_zero() {
+        return Ok(None);
+    }
+
+    let tester = IFlashLoanTester::new(contract, client.owner.clone());
+    let call = tester.withdraw(report.token, report.surplus);
+    let receipt = TxBuilder::call(client, &client.owner, call)
+        .send_and_confirm()
+        .await?;
+    Ok(Some(receipt))
+}
@@ -0,0 +1,383 @@
+//! Recording and playback of JSON-RPC sessions.
+//!
+//! `Recorder` sits in front of the real transport and writes every request
+//! and its response to a JSON file. `Playback` serves a recorded session back
+//! without touching the network, so a user's exact run can be reproduced
+//! offline. Each request is answered by the first unserved exchange with the
+//! same method and params, so requests that were sent concurrently replay
+//! whatever order they arrive in. Repeated identical requests, such as
+//! receipt polls, are still answered in the order they were recorded.
+
+use async_trait::async_trait;
+use ethers::providers::{HttpClientError, JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// One request and what the node answered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    pub params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RecordedError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordedError {
+    /// The node returned a JSON-RPC error response (reverts, nonce errors...).
+    Rpc {
+        code: i64,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<Value>,
+    },
+    /// The request failed before a response was received.
+    Transport(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Recorder<T> {
+    inner: T,
+    session: Option<Arc<Session>>,
+}
+
+#[derive(Debug)]
+struct Session {
+    path: PathBuf,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl<T> Recorder<T> {
+    /// Record to `path` when given, otherwise pass requests straight through.
+    pub fn new(inner: T, path: Option<PathBuf>) -> Self {
+        let session = path.map(|path| {
+            Arc::new(Session {
+                path,
+                exchanges: Mutex::new(Vec::new()),
+            })
+        });
+        Self { inner, session }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl Session {
+    // The file is rewritten after every exchange so a crashed run still
+    // leaves a usable recording behind.
+    fn push(&self, exchange: Exchange) -> Result<(), ProviderError> {
+        let mut exchanges = self.exchanges.lock().expect("recorder lock poisoned");
+        exchanges.push(exchange);
+        let json = serde_json::to_string_pretty(&*exchanges)?;
+        fs::write(&self.path, json).map_err(|e| {
+            ProviderError::CustomError(format!("failed to write {}: {}", self.path.display(), e))
+        })
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient> JsonRpcClient for Recorder<T> {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let Some(session) = &self.session else {
+            return self.inner.request(method, params).await.map_err(Into::into);
+        };
+
+        let recorded_params = serde_json::to_value(&params)?;
+        let response: Result<Value, T::Error> = self.inner.request(method, params).await;
+
+        let mut exchange = Exchange {
+            method: method.to_string(),
+            params: recorded_params,
+            result: None,
+            error: None,
+        };
+        match &response {
+            Ok(value) => exchange.result = Some(value.clone()),
+            Err(e) => {
+                exchange.error = Some(match e.as_error_response() {
+                    Some(rpc) => RecordedError::Rpc {
+                        code: rpc.code,
+                        message: rpc.message.clone(),
+                        data: rpc.data.clone(),
+                    },
+                    None => RecordedError::Transport(e.to_string()),
+                })
+            }
+        }
+        session.push(exchange)?;
+
+        Ok(serde_json::from_value(response.map_err(Into::into)?)?)
+    }
+}
+
+/// Serves a recorded session back, matching each request to its exchange.
+#[derive(Debug)]
+pub struct Playback {
+    path: PathBuf,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl Playback {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProviderError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| {
+            ProviderError::CustomError(format!("failed to read {}: {}", path.display(), e))
+        })?;
+        let exchanges: Vec<Exchange> = serde_json::from_str(&contents)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            exchanges: Mutex::new(exchanges),
+        })
+    }
+
+    /// Exchanges not yet served.
+    pub fn remaining(&self) -> usize {
+        self.exchanges.lock().expect("playback lock poisoned").len()
+    }
+
+    fn next(&self, method: &str, params: &Value) -> Result<Exchange, ProviderError> {
+        let mut exchanges = self.exchanges.lock().expect("playback lock poisoned");
+        if exchanges.is_empty() {
+            return Err(ProviderError::CustomError(format!(
+                "{} has no more recorded requests (wanted {})",
+                self.path.display(),
+                method
+            )));
+        }
+        let Some(index) = exchanges
+            .iter()
+            .position(|exchange| exchange.method == method && exchange.params == *params)
+        else {
+            return Err(ProviderError::CustomError(format!(
+                "replay diverged: {} {} was not recorded",
+                method, params
+            )));
+        };
+        Ok(exchanges.remove(index))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for Playback {
+    type Error = ProviderError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(&params)?;
+        let exchange = self.next(method, &params)?;
+
+        match (exchange.result, exchange.error) {
+            (
+                _,
+                Some(RecordedError::Rpc {
+                    code,
+                    message,
+                    data,
+                }),
+            ) => {
+                let err = JsonRpcError {
+                    code,
+                    message,
+                    data,
+                };
+                Err(ProviderError::JsonRpcClientError(Box::new(
+                    HttpClientError::JsonRpcError(err),
+                )))
+            }
+            (_, Some(RecordedError::Transport(message))) => {
+                Err(ProviderError::CustomError(message))
+            }
+            (result, None) => Ok(serde_json::from_value(result.unwrap_or(Value::Null))?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        providers::{Middleware, Provider},
+        types::{transaction::eip2718::TypedTransaction, Address, TransactionRequest, U256, U64},
+    };
+
+    /// Answers a few methods the way a node would.
+    #[derive(Debug)]
+    struct Node;
+
+    #[async_trait]
+    impl JsonRpcClient for Node {
+        type Error = ProviderError;
+
+        async fn request<P, R>(&self, method: &str, _params: P) -> Result<R, Self::Error>
+        where
+            P: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned + Send,
+        {
+            let result = match method {
+                "eth_blockNumber" => Value::from("0x10"),
+                "eth_chainId" => Value::from("0x2612"),
+                "eth_call" => {
+                    return Err(ProviderError::JsonRpcClientError(Box::new(
+                        HttpClientError::JsonRpcError(JsonRpcError {
+                            code: 3,
+                            message: "execution reverted".into(),
+                            data: Some(Value::from("0x")),
+                        }),
+                    )))
+                }
+                _ => return Err(ProviderError::CustomError(format!("no {}", method))),
+            };
+            Ok(serde_json::from_value(result)?)
+        }
+    }
+
+    fn session_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "capacitor-record-{}-{}.json",
+            std::process::id(),
+            name
+        ))
+    }
+
+    fn call() -> TypedTransaction {
+        TransactionRequest::new().to(Address::zero()).into()
+    }
+
+    async fn run<P: JsonRpcClient>(provider: &Provider<P>) -> (U64, U256, String) {
+        let block = provider.get_block_number().await.unwrap();
+        let chain = provider.get_chainid().await.unwrap();
+        let revert = provider.call(&call(), None).await.unwrap_err();
+        let code = revert.as_error_response().map(|e| e.code).unwrap();
+        (block, chain, format!("{} {}", code, revert))
+    }
+
+    /// Record `run` against [`Node`] and return what it saw.
+    async fn record(path: &Path) -> (U64, U256, String) {
+        let provider = Provider::new(Recorder::new(Node, Some(path.to_path_buf())));
+        run(&provider).await
+    }
+
+    #[tokio::test]
+    async fn a_recorded_session_replays_identically() {
+        let path = session_path("round-trip");
+        let live = record(&path).await;
+
+        let playback = Arc::new(Playback::load(&path).unwrap());
+        assert_eq!(playback.remaining(), 3);
+        let replayed = run(&Provider::new(playback.clone())).await;
+
+        assert_eq!(replayed, live);
+        assert_eq!(playback.remaining(), 0);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn requests_replay_in_any_order() {
+        let path = session_path("any-order");
+        let live = record(&path).await;
+
+        let playback = Arc::new(Playback::load(&path).unwrap());
+        let provider = Provider::new(playback.clone());
+        let tx = call();
+        // Sent concurrently, the way prefetched reads are
+        let (revert, chain, block) = futures::join!(
+            provider.call(&tx, None),
+            provider.get_chainid(),
+            provider.get_block_number(),
+        );
+        assert_eq!((block.unwrap(), chain.unwrap()), (live.0, live.1));
+        assert_eq!(revert.unwrap_err().as_error_response().unwrap().code, 3);
+        assert_eq!(playback.remaining(), 0);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn repeated_requests_replay_in_recorded_order() {
+        let path = session_path("repeated");
+        let exchange = |block: &str| Exchange {
+            method: "eth_blockNumber".into(),
+            params: Value::Null,
+            result: Some(Value::from(block)),
+            error: None,
+        };
+        let session = vec![exchange("0x1"), exchange("0x2")];
+        fs::write(&path, serde_json::to_string(&session).unwrap()).unwrap();
+
+        let playback = Provider::new(Playback::load(&path).unwrap());
+        assert_eq!(playback.get_block_number().await.unwrap(), U64::from(1));
+        assert_eq!(playback.get_block_number().await.unwrap(), U64::from(2));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn transport_failures_are_recorded_and_replayed() {
+        let path = session_path("transport");
+        let provider = Provider::new(Recorder::new(Node, Some(path.clone())));
+        let live = provider
+            .request::<_, Value>("eth_gasPrice", ())
+            .await
+            .unwrap_err();
+
+        let playback = Provider::new(Playback::load(&path).unwrap());
+        let replayed = playback
+            .request::<_, Value>("eth_gasPrice", ())
+            .await
+            .unwrap_err();
+        assert!(replayed.as_error_response().is_none());
+        assert!(replayed.to_string().ends_with(&live.to_string()));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn playback_rejects_a_request_that_was_not_recorded() {
+        let path = session_path("diverged");
+        record(&path).await;
+
+        let playback = Provider::new(Playback::load(&path).unwrap());
+        let err = playback.get_gas_price().await.unwrap_err();
+        assert!(err.to_string().contains("replay diverged"), "{}", err);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn playback_fails_once_the_session_is_used_up() {
+        let path = session_path("exhausted");
+        record(&path).await;
+
+        let playback = Provider::new(Playback::load(&path).unwrap());
+        run(&playback).await;
+        let err = playback.get_block_number().await.unwrap_err();
+        assert!(
+            err.to_string().contains("no more recorded requests"),
+            "{}",
+            err
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recording_is_off_without_a_path() {
+        let provider = Provider::new(Recorder::new(Node, None));
+        assert_eq!(provider.get_block_number().await.unwrap(), U64::from(16));
+    }
+}
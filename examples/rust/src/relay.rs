@@ -0,0 +1,66 @@
+//! Submitting transactions through a private relay.
+//!
+//! Flash loan transactions in the public mempool can be copied and
+//! front-run. With `submit = "private"` the transaction is signed locally
+//! and sent with `eth_sendRawTransaction` to `relay_url` only; inclusion
+//! is then watched on the regular RPC endpoints.
+//!
+//! The relay sees the signed transaction before anyone else, so it has to
+//! be trusted not to front-run or leak it. It may also decline to forward
+//! a transaction without telling you, which shows up as a receipt timeout.
+
+use crate::client::SignerClient;
+use ethers::{prelude::*, types::transaction::eip2718::TypedTransaction};
+use eyre::Result;
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+/// Where signed transactions are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmitMode {
+    /// The configured RPC endpoints, and from there the public mempool.
+    #[default]
+    Public,
+    /// `relay_url` only.
+    Private,
+}
+
+impl fmt::Display for SubmitMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SubmitMode::Public => "public",
+            SubmitMode::Private => "private",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RelaySubmitter {
+    url: String,
+    relay: Http,
+}
+
+impl RelaySubmitter {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            relay: Http::from_str(url)?,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Fill in `tx` from `signer`, sign it locally and hand it to the relay.
+    ///
+    /// Returns the transaction hash; nothing is sent to the public nodes.
+    pub async fn submit(&self, signer: &SignerClient, mut tx: TypedTransaction) -> Result<H256> {
+        signer.fill_transaction(&mut tx, None).await?;
+        let signature = signer.signer().sign_transaction(&tx).await?;
+        let raw = tx.rlp_signed(&signature);
+        let hash = JsonRpcClient::request(&self.relay, "eth_sendRawTransaction", [raw]).await?;
+        Ok(hash)
+    }
+}
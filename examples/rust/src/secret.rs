@@ -0,0 +1,74 @@
+//! Keeping secrets out of logs.
+
+use serde::Deserialize;
+use std::fmt;
+
+/// A secret (private key, signature) that never prints its contents.
+///
+/// Both `Debug` and `Display` render `0x***`; use [`Redacted::expose`] at the
+/// one place the raw value is needed.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Redacted<T>(T);
+
+impl<T> Redacted<T> {
+    pub fn new(secret: T) -> Self {
+        Self(secret)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("0x***")
+    }
+}
+
+impl<T> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("0x***")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    fn leaks(output: &str) -> bool {
+        let bytes = KEY.trim_start_matches("0x");
+        // Any 8 consecutive hex digits of the key count as a leak
+        (0..=bytes.len() - 8).any(|i| output.contains(&bytes[i..i + 8]))
+    }
+
+    #[test]
+    fn formatting_never_shows_the_key() {
+        let redacted = Redacted::new(KEY.to_string());
+        for output in [
+            format!("{:?}", redacted),
+            format!("{:#?}", redacted),
+            format!("{}", redacted),
+        ] {
+            assert_eq!(output, "0x***");
+            assert!(!leaks(&output));
+        }
+        assert_eq!(redacted.expose(), KEY);
+    }
+
+    #[test]
+    fn a_debug_printed_config_does_not_leak_keys() {
+        let config: Config = toml::from_str(&format!(
+            "owner_key = \"{}\"\nfunder_key = \"{}\"",
+            KEY, KEY
+        ))
+        .unwrap();
+        let output = format!("{:?}", config);
+        assert!(!leaks(&output), "{}", output);
+        assert_eq!(config.owner_key(), Some(KEY));
+    }
+}
@@ -0,0 +1,192 @@
+//! Checking a deployed tester in every mode.
+//!
+//! The failure modes are simulated with `eth_call`: they can only revert,
+//! so sending them would just burn gas. The success mode is simulated and
+//! then sent for real, which checks the fee path on-chain as well.
+
+use crate::{
+    abi::{IFlashLoanTester, IERC20},
+    amount::TokenAmount,
+    client::FlashLoanClient,
+    flash_loan::{execute_flash_loan, FlashLoanMode, FlashLoanParams},
+    send::TxBuilder,
+};
+use ethers::prelude::*;
+use eyre::Result;
+
+/// How one mode behaved.
+#[derive(Debug, Clone)]
+pub struct ModeCheck {
+    pub mode: FlashLoanMode,
+    /// Whether the provider accepted the loan.
+    pub succeeded: bool,
+    /// Decoded revert reason when it did not.
+    pub reason: Option<String>,
+    /// The on-chain run, for the success mode.
+    pub tx_hash: Option<H256>,
+}
+
+impl ModeCheck {
+    /// The mode behaved as the contract intends: `SUCCESS` went through,
+    /// and the failure modes reverted for the reason they are meant to.
+    pub fn passed(&self) -> bool {
+        match self.mode.expected_revert() {
+            None => self.succeeded,
+            Some(words) => !self.succeeded && self.reason_matches(words),
+        }
+    }
+
+    /// A failure mode reverted, but not for its own reason.
+    pub fn wrong_reason(&self) -> bool {
+        !self.succeeded && !self.mode.should_succeed() && !self.passed()
+    }
+
+    fn reason_matches(&self, words: &[&str]) -> bool {
+        let Some(reason) = &self.reason else {
+            return false;
+        };
+        let reason = reason.to_lowercase();
+        words.iter().any(|word| reason.contains(word))
+    }
+}
+
+/// Top `tester` up to `fee`, sent by the funder.
+///
+/// Only the shortfall is transferred. Returns `None` when the tester
+/// already holds enough.
+pub async fn fund_fee(
+    client: &FlashLoanClient,
+    tester: Address,
+    token: Address,
+    fee: TokenAmount,
+) -> Result<Option<TransactionReceipt>> {
+    let erc20 = IERC20::new(token, client.funder.clone());
+    let need = fee.raw();
+    let have = erc20.balance_of(tester).await?;
+    if have >= need {
+        return Ok(None);
+    }
+
+    let transfer = erc20.transfer(tester, need - have);
+    let receipt = TxBuilder::call(client, &client.funder, transfer)
+        .send_and_confirm()
+        .await?;
+    Ok(Some(receipt))
+}
+
+/// Run a loan of `amount` in every `FlashLoanMode`.
+///
+/// The tester must hold `fee`, the provider's fee for `amount`, already;
+/// see [`fund_fee`]. Modes that
+/// behave unexpectedly are reported in the result rather than as errors.
+pub async fn self_test(
+    client: &FlashLoanClient,
+    tester: Address,
+    token: Address,
+    amount: TokenAmount,
+    fee: TokenAmount,
+) -> Result<Vec<ModeCheck>> {
+    let contract = IFlashLoanTester::new(tester, client.owner.clone());
+
+    let mut checks = Vec::new();
+    for mode in FlashLoanMode::ALL {
+        let (succeeded, reason) = match contract
+            .test_flash_loan(token, amount.raw(), mode.into())
+            .call()
+            .await
+        {
+            Ok(()) => (true, None),
+            Err(e) => (false, Some(revert_reason(&e))),
+        };
+        checks.push(ModeCheck {
+            mode,
+            succeeded,
+            reason,
+            tx_hash: None,
+        });
+    }
+
+    // Runs last, since paying the fee spends the funding
+    let success = checks
+        .iter_mut()
+        .find(|check| check.mode == FlashLoanMode::Success && check.succeeded);
+    if let Some(check) = success {
+        let params = FlashLoanParams {
+            tester,
+            token,
+            amount,
+            expected_fee: fee,
+            mode: FlashLoanMode::Success.into(),
+            access_list: None,
+        };
+        let outcome = execute_flash_loan(client, &params).await?;
+        check.tx_hash = Some(outcome.tx_hash);
+        if !outcome.success {
+            check.succeeded = false;
+            check.reason = Some("reverted on-chain".to_string());
+        }
+    }
+
+    Ok(checks)
+}
+
+/// The `Error(string)` reason of a revert, or a description of what else
+/// went wrong.
+pub fn revert_reason<M: Middleware>(error: &ContractError<M>) -> String {
+    if let Some(reason) = error.decode_revert::<String>() {
+        return reason;
+    }
+    match error.as_revert() {
+        Some(data) if !data.is_empty() => format!("custom error {}", data),
+        Some(_) => "reverted without a reason".to_string(),
+        None => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(mode: FlashLoanMode, reason: Option<&str>) -> ModeCheck {
+        ModeCheck {
+            mode,
+            succeeded: reason.is_none(),
+            reason: reason.map(str::to_string),
+            tx_hash: None,
+        }
+    }
+
+    #[test]
+    fn failure_modes_pass_only_with_their_own_revert_reason() {
+        let no_repay = FlashLoanMode::FailNoRepay;
+        assert!(check(no_repay, Some("Flash loan callback failed")).passed());
+        assert!(check(no_repay, Some("executeOperation returned false")).passed());
+        assert!(!check(no_repay, Some("Fee not repaid")).passed());
+
+        let partial = FlashLoanMode::FailPartial;
+        assert!(check(partial, Some("Flash loan not repaid")).passed());
+        assert!(check(partial, Some("Insufficient fee")).passed());
+        assert!(!check(partial, Some("Callback failed")).passed());
+    }
+
+    #[test]
+    fn reverts_from_the_tester_itself_do_not_pass() {
+        for mode in [FlashLoanMode::FailNoRepay, FlashLoanMode::FailPartial] {
+            for reason in ["Not owner", "Transfer failed", "reverted without a reason"] {
+                let check = check(mode, Some(reason));
+                assert!(!check.passed(), "{} passed on {:?}", mode, reason);
+                assert!(check.wrong_reason());
+            }
+        }
+    }
+
+    #[test]
+    fn success_must_go_through() {
+        assert!(check(FlashLoanMode::Success, None).passed());
+        assert!(!check(FlashLoanMode::Success, Some("Not owner")).passed());
+        // Going through is wrong for a failure mode, whatever the reason
+        let accepted = check(FlashLoanMode::FailPartial, None);
+        assert!(!accepted.passed());
+        assert!(!accepted.wrong_reason());
+    }
+}
@@ -0,0 +1,137 @@
+//! One way of sending transactions.
+//!
+//! Every send takes the same steps in the same order: set the transaction
+//! type, attach the access list, pin the nonce, fill the gas limit, submit
+//! through the RPC or the private relay, and wait for the receipt.
+//! [`TxBuilder`] runs them with the client's settings unless told
+//! otherwise, so a transfer, a deployment and a flash loan all go out the
+//! same way.
+
+use crate::{
+    access_list::{with_access_list, LEGACY_NO_ACCESS_LIST},
+    client::{FlashLoanClient, SignerClient, Transport},
+    gas::{estimate_cost, estimate_limit_and_cost, fill_gas_limit, GasPolicy},
+    poll::{confirm_receipt, PollPolicy},
+    tx::ResolvedTxType,
+};
+use ethers::{
+    contract::ContractCall,
+    providers::PendingTransaction,
+    types::{
+        transaction::{eip2718::TypedTransaction, eip2930::AccessList},
+        TransactionReceipt, U256,
+    },
+};
+use eyre::Result;
+use tracing::warn;
+
+/// A transaction to be sent by one of the client's signers.
+#[derive(Debug, Clone)]
+pub struct TxBuilder<'a> {
+    client: &'a FlashLoanClient,
+    signer: &'a SignerClient,
+    tx: TypedTransaction,
+    access_list: Option<AccessList>,
+    nonce: Option<U256>,
+    gas: GasPolicy,
+    poll: PollPolicy,
+}
+
+impl<'a> TxBuilder<'a> {
+    /// Send `tx` from `signer`, one of `client.owner` and `client.funder`.
+    pub fn new(
+        client: &'a FlashLoanClient,
+        signer: &'a SignerClient,
+        tx: impl Into<TypedTransaction>,
+    ) -> Self {
+        Self {
+            client,
+            signer,
+            tx: tx.into(),
+            access_list: None,
+            nonce: None,
+            gas: client.gas,
+            poll: client.poll,
+        }
+    }
+
+    /// Send a prepared contract call from `signer`.
+    pub fn call<D>(
+        client: &'a FlashLoanClient,
+        signer: &'a SignerClient,
+        call: ContractCall<SignerClient, D>,
+    ) -> Self {
+        Self::new(client, signer, call.tx)
+    }
+
+    /// Attach `list`, unless the client sends legacy transactions, which
+    /// can't carry one. Then the list is dropped with a warning.
+    pub fn access_list(mut self, list: AccessList) -> Self {
+        if self.client.tx_type == ResolvedTxType::Legacy {
+            warn!("{}, sending without it", LEGACY_NO_ACCESS_LIST);
+            return self;
+        }
+        self.access_list = Some(list);
+        self
+    }
+
+    /// Use `nonce` instead of the signer's next pending nonce.
+    pub fn nonce(mut self, nonce: impl Into<U256>) -> Self {
+        self.nonce = Some(nonce.into());
+        self
+    }
+
+    /// Gas settings instead of the client's.
+    pub fn gas(mut self, policy: GasPolicy) -> Self {
+        self.gas = policy;
+        self
+    }
+
+    /// Receipt polling, timeout and confirmations instead of the client's.
+    pub fn poll(mut self, policy: PollPolicy) -> Self {
+        self.poll = policy;
+        self
+    }
+
+    /// The transaction as it will be sent, before the gas limit is set.
+    pub fn tx(&self) -> TypedTransaction {
+        let mut tx = self.tx.clone();
+        self.client.tx_type.apply(&mut tx);
+        if let Some(list) = &self.access_list {
+            with_access_list(&mut tx, list.clone());
+        }
+        if let Some(nonce) = self.nonce {
+            tx.set_nonce(nonce);
+        }
+        tx
+    }
+
+    /// Cost in wei of sending this transaction at the current gas price.
+    pub async fn estimate_cost(&self) -> Result<U256> {
+        estimate_cost(self.signer, &self.tx(), self.gas).await
+    }
+
+    /// The gas limit `send` would set, and the cost in wei at that limit.
+    pub async fn estimate_limit_and_cost(&self) -> Result<(U256, U256)> {
+        estimate_limit_and_cost(self.signer, &self.tx(), self.gas).await
+    }
+
+    /// Fill in everything but the signature and submit the transaction.
+    pub async fn send(self) -> Result<PendingTransaction<'a, Transport>> {
+        let mut tx = self.tx();
+        fill_gas_limit(self.signer, &mut tx, self.gas).await?;
+        self.client.send_transaction(self.signer, tx).await
+    }
+
+    /// [`send`](Self::send), then wait for the receipt and the poll
+    /// policy's confirmations; see [`confirm_receipt`].
+    ///
+    /// A reverted transaction still returns its receipt; check `status`.
+    /// Fails when the transaction is dropped from the mempool, and with
+    /// `CapacitorError::Timeout` when it isn't confirmed in time.
+    pub async fn send_and_confirm(self) -> Result<TransactionReceipt> {
+        let (client, poll) = (self.client, self.poll);
+        let tx_hash = self.send().await?.tx_hash();
+        confirm_receipt(&client.provider, tx_hash, poll).await
+    }
+}
@@ -0,0 +1,118 @@
+//! Signing behind a trait, so keys don't have to live in this process.
+//!
+//! Everything that sends goes through [`FlashLoanClient`], whose
+//! middleware signs with a [`BoxedSigner`]. Wrap any [`CapacitorSigner`]
+//! in one, such as a KMS, HSM or remote signing service, and pass it to
+//! [`FlashLoanClient::connect_with`]. The flows themselves don't change.
+//! `LocalWallet` implements the trait and is what `connect` uses for the
+//! configured keys.
+//!
+//! [`FlashLoanClient`]: crate::client::FlashLoanClient
+//! [`FlashLoanClient::connect_with`]: crate::client::FlashLoanClient::connect_with
+
+use async_trait::async_trait;
+use ethers::{
+    signers::{LocalWallet, Signer},
+    types::{
+        transaction::{eip2718::TypedTransaction, eip712::Eip712},
+        Address, Signature,
+    },
+};
+use eyre::Result;
+use std::{fmt::Debug, sync::Arc};
+use thiserror::Error;
+
+/// Signs transactions for one account.
+#[async_trait]
+pub trait CapacitorSigner: Debug + Send + Sync {
+    /// Account whose transactions this signer signs.
+    fn address(&self) -> Address;
+
+    /// Sign `tx`, which has its chain ID, nonce, gas and fees filled in.
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature>;
+}
+
+#[async_trait]
+impl CapacitorSigner for LocalWallet {
+    fn address(&self) -> Address {
+        Signer::address(self)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature> {
+        Ok(Signer::sign_transaction(self, tx).await?)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SignerError {
+    #[error("Signing failed: {0}")]
+    Signing(String),
+
+    #[error("{0} is not supported by this signer")]
+    Unsupported(&'static str),
+}
+
+/// Any [`CapacitorSigner`], usable wherever ethers expects a `Signer`.
+///
+/// Only transactions can be signed; messages and typed data fail with
+/// `SignerError::Unsupported`.
+#[derive(Debug, Clone)]
+pub struct BoxedSigner {
+    inner: Arc<dyn CapacitorSigner>,
+    chain_id: u64,
+}
+
+impl BoxedSigner {
+    pub fn new(signer: impl CapacitorSigner + 'static, chain_id: u64) -> Self {
+        Self {
+            inner: Arc::new(signer),
+            chain_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for BoxedSigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        _message: S,
+    ) -> Result<Signature, Self::Error> {
+        Err(SignerError::Unsupported("message signing"))
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        // Remote signers may not know the chain, so it is always set
+        let mut tx = tx.clone();
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+        self.inner
+            .sign_transaction(&tx)
+            .await
+            .map_err(|e| SignerError::Signing(format!("{:#}", e)))
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        _payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        Err(SignerError::Unsupported("typed data signing"))
+    }
+
+    fn address(&self) -> Address {
+        self.inner.address()
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        Self {
+            chain_id: chain_id.into(),
+            ..self
+        }
+    }
+}
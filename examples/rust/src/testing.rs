@@ -0,0 +1,261 @@
+//! Helpers for running flows against a local node.
+//!
+//! Loans, withdrawals and batches all change on-chain state. Wrapping each
+//! run in a snapshot keeps runs independent: state is rolled back to the
+//! snapshot afterwards, whatever the run did.
+//!
+//! Where Anvil is more than a test needs, [`MockNode`] serves JSON-RPC over
+//! HTTP from a handler function and records which methods were called, and
+//! how many requests it was serving at once.
+
+use ethers::{
+    providers::{JsonRpcError, Middleware},
+    types::U256,
+};
+use eyre::{eyre, Result};
+use serde_json::{json, Value};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// Take an `evm_snapshot` and return its id.
+pub async fn anvil_snapshot<M: Middleware>(provider: &M) -> Result<U256> {
+    let id = provider.provider().request("evm_snapshot", ()).await?;
+    Ok(id)
+}
+
+/// Roll the chain back to snapshot `id` with `evm_revert`.
+pub async fn anvil_revert<M: Middleware>(provider: &M, id: U256) -> Result<()> {
+    let reverted: bool = provider.provider().request("evm_revert", [id]).await?;
+    if !reverted {
+        return Err(eyre!("evm_revert rejected snapshot {}", id));
+    }
+    Ok(())
+}
+
+/// Run `f` inside a snapshot and revert once it has finished.
+///
+/// The revert happens even when `f` fails; the error from `f` takes
+/// precedence over a failed revert.
+pub async fn with_snapshot<M, F, Fut, T>(provider: &M, f: F) -> Result<T>
+where
+    M: Middleware,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let id = anvil_snapshot(provider).await?;
+    let result = f().await;
+    let reverted = anvil_revert(provider, id).await;
+    let value = result?;
+    reverted?;
+    Ok(value)
+}
+
+type Handler = dyn Fn(&str, &Value) -> Result<Value, JsonRpcError> + Send + Sync;
+type Delay = dyn Fn(&str, &Value) -> Duration + Send + Sync;
+
+/// A JSON-RPC endpoint on localhost answering from a handler function.
+///
+/// The handler gets the method and params of each request and returns the
+/// result, or the error response to send. The server stops when the
+/// `MockNode` is dropped.
+#[derive(Debug)]
+pub struct MockNode {
+    url: String,
+    calls: Arc<Mutex<Vec<String>>>,
+    in_flight: Arc<InFlight>,
+    server: JoinHandle<()>,
+}
+
+// Requests being served now, and the most there have been at once
+#[derive(Debug, Default)]
+struct InFlight {
+    now: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl MockNode {
+    pub async fn start<F>(handler: F) -> Result<Self>
+    where
+        F: Fn(&str, &Value) -> Result<Value, JsonRpcError> + Send + Sync + 'static,
+    {
+        Self::start_with_latency(Duration::ZERO, handler).await
+    }
+
+    /// Like [`start`](Self::start), but every response is delayed by `latency`.
+    pub async fn start_with_latency<F>(latency: Duration, handler: F) -> Result<Self>
+    where
+        F: Fn(&str, &Value) -> Result<Value, JsonRpcError> + Send + Sync + 'static,
+    {
+        Self::start_with_delays(move |_, _| latency, handler).await
+    }
+
+    /// Like [`start`](Self::start), but each response is delayed by what
+    /// `delay` returns for its method and params, so later requests can be
+    /// answered first.
+    pub async fn start_with_delays<D, F>(delay: D, handler: F) -> Result<Self>
+    where
+        D: Fn(&str, &Value) -> Duration + Send + Sync + 'static,
+        F: Fn(&str, &Value) -> Result<Value, JsonRpcError> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let in_flight = Arc::new(InFlight::default());
+        let handler: Arc<Handler> = Arc::new(handler);
+        let delay: Arc<Delay> = Arc::new(delay);
+
+        let log = calls.clone();
+        let serving = in_flight.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let delay = delay.clone();
+                let log = log.clone();
+                let serving = serving.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, &*delay, &*handler, &log, &serving).await;
+                });
+            }
+        });
+        Ok(Self {
+            url,
+            calls,
+            in_flight,
+            server,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Methods called so far, in the order the requests arrived.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().expect("mock node lock poisoned").clone()
+    }
+
+    /// How many times `method` has been called.
+    pub fn count(&self, method: &str) -> usize {
+        self.calls()
+            .iter()
+            .filter(|called| *called == method)
+            .count()
+    }
+
+    /// The most requests that were being answered at the same time.
+    pub fn max_in_flight(&self) -> usize {
+        self.in_flight.peak.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockNode {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// A URL nothing listens on, for an endpoint that is down.
+pub async fn dead_endpoint() -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    Ok(format!("http://{}", listener.local_addr()?))
+}
+
+/// The error response a node sends for a reverted call.
+pub fn revert_error(data: impl Into<String>) -> JsonRpcError {
+    JsonRpcError {
+        code: 3,
+        message: "execution reverted".into(),
+        data: Some(Value::from(data.into())),
+    }
+}
+
+// One request per connection: read the headers and the body they announce,
+// answer, and close.
+async fn serve(
+    mut stream: TcpStream,
+    delay: &Delay,
+    handler: &Handler,
+    calls: &Mutex<Vec<String>>,
+    in_flight: &InFlight,
+) -> Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let (header_end, length) = loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(eyre!("connection closed before the request was complete"));
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            let headers = String::from_utf8_lossy(&buffer[..end]).to_ascii_lowercase();
+            let length = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            break (end + 4, length);
+        }
+    };
+    while buffer.len() < header_end + length {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    let request: Value = serde_json::from_slice(&buffer[header_end..])?;
+    let method = request["method"].as_str().unwrap_or_default().to_string();
+    calls
+        .lock()
+        .expect("mock node lock poisoned")
+        .push(method.clone());
+    let _serving = Serving::enter(in_flight);
+    let latency = delay(&method, &request["params"]);
+    let response = match handler(&method, &request["params"]) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": error.code, "message": error.message, "data": error.data },
+        }),
+    };
+    tokio::time::sleep(latency).await;
+
+    let body = response.to_string();
+    let reply = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    stream.write_all(reply.as_bytes()).await?;
+    Ok(())
+}
+
+// Counts a request as in flight from when it is read until it is answered
+struct Serving<'a>(&'a InFlight);
+
+impl<'a> Serving<'a> {
+    fn enter(in_flight: &'a InFlight) -> Self {
+        let now = in_flight.now.fetch_add(1, Ordering::SeqCst) + 1;
+        in_flight.peak.fetch_max(now, Ordering::SeqCst);
+        Self(in_flight)
+    }
+}
+
+impl Drop for Serving<'_> {
+    fn drop(&mut self) {
+        self.0.now.fetch_sub(1, Ordering::SeqCst);
+    }
+}
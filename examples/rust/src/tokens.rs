@@ -0,0 +1,215 @@
+//! Token symbol lookup.
+//!
+//! Lets users pass `TUSDT` wherever a token address is expected. Each
+//! network starts with the tokens it is known to have; `[tokens.<network>]`
+//! tables in `capacitor.toml` add to or override them.
+//!
+//! [`token_decimals`] reads a token's decimals for tokens that may not
+//! implement `decimals()`, using `[decimals_override]` from the config.
+
+use crate::{
+    abi::IERC20,
+    config::{Config, TUSDT_TOKEN},
+    error::CapacitorError,
+    fee_resolver::is_transport_error,
+    network::Network,
+    raw::call_raw,
+};
+use ethers::{
+    abi::Token,
+    providers::Middleware,
+    types::{Address, U256},
+};
+use eyre::{eyre, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tracing::warn;
+
+/// Decimals assumed for a token that reports none and has no override.
+pub const DEFAULT_DECIMALS: u8 = 18;
+
+#[derive(Debug, Clone, Default)]
+pub struct TokenBook {
+    tokens: HashMap<Network, BTreeMap<String, Address>>,
+}
+
+impl TokenBook {
+    /// The built-in tokens for every network.
+    pub fn builtin() -> Self {
+        let mut book = Self::default();
+        book.insert(
+            Network::PlasmaTestnet,
+            "TUSDT",
+            TUSDT_TOKEN.parse().expect("valid token address"),
+        );
+        book
+    }
+
+    /// The built-in tokens plus those listed in the config.
+    pub fn from_config(config: &Config) -> Self {
+        let mut book = Self::builtin();
+        for network in [Network::PlasmaTestnet, Network::PlasmaMainnet] {
+            for (symbol, address) in config.tokens.get(network.key()).into_iter().flatten() {
+                book.insert(network, symbol, *address);
+            }
+        }
+        book
+    }
+
+    pub fn insert(&mut self, network: Network, symbol: &str, address: Address) {
+        self.tokens
+            .entry(network)
+            .or_default()
+            .insert(symbol.to_uppercase(), address);
+    }
+
+    /// Symbols known on `network`, sorted.
+    pub fn symbols(&self, network: Network) -> Vec<&str> {
+        self.tokens
+            .get(&network)
+            .map(|tokens| tokens.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// The symbol `address` is known by on `network`, if any.
+    pub fn symbol_of(&self, network: Network, address: Address) -> Option<&str> {
+        self.tokens
+            .get(&network)?
+            .iter()
+            .find_map(|(symbol, known)| (*known == address).then_some(symbol.as_str()))
+    }
+
+    /// Resolve a symbol (case-insensitive) or a `0x` address.
+    pub fn resolve(&self, network: Network, input: &str) -> Result<Address, CapacitorError> {
+        if input.starts_with("0x") {
+            return input
+                .parse()
+                .map_err(|_| CapacitorError::InvalidAddress(input.to_string()));
+        }
+
+        self.tokens
+            .get(&network)
+            .and_then(|tokens| tokens.get(&input.to_uppercase()))
+            .copied()
+            .ok_or_else(|| CapacitorError::UnknownToken {
+                symbol: input.to_string(),
+                network,
+                known: self.symbols(network).join(", "),
+            })
+    }
+}
+
+/// Symbol of `token`, or its address when `symbol()` fails.
+pub async fn token_symbol<M: Middleware + 'static>(client: Arc<M>, token: Address) -> String {
+    IERC20::new(token, client)
+        .symbol()
+        .await
+        .unwrap_or_else(|_| format!("{:?}", token))
+}
+
+/// Decimals of `token`.
+///
+/// When `decimals()` reverts or returns something that isn't a `uint8`, as
+/// it does for some minimal and proxy tokens, the decimals in `overrides`
+/// are used instead, and failing that [`DEFAULT_DECIMALS`] with a warning.
+/// Transport errors are returned, since they say nothing about the token.
+pub async fn token_decimals<M: Middleware>(
+    client: Arc<M>,
+    token: Address,
+    overrides: &HashMap<Address, u8>,
+) -> Result<u8>
+where
+    M::Error: 'static,
+{
+    let error = match call_raw(&*client, token, "decimals()(uint8)", Vec::new()).await {
+        Ok(output) => match output.as_slice() {
+            [Token::Uint(value)] if *value <= U256::from(u8::MAX) => {
+                return Ok(value.as_u32() as u8)
+            }
+            _ => eyre!("decimals() returned {:?}", output),
+        },
+        Err(e) if is_transport_error::<M>(&e) => return Err(e),
+        Err(e) => e,
+    };
+    Ok(match overrides.get(&token) {
+        Some(decimals) => *decimals,
+        None => {
+            warn!(
+                ?token,
+                %error,
+                "decimals() failed and no decimals_override is set, assuming {}",
+                DEFAULT_DECIMALS
+            );
+            DEFAULT_DECIMALS
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{dead_endpoint, revert_error, MockNode};
+    use ethers::providers::{Http, Provider};
+    use serde_json::Value;
+
+    const TOKEN: Address = Address::repeat_byte(0x11);
+
+    /// A node whose `eth_call` returns `output`, or reverts with `Err(data)`.
+    async fn node(output: Result<String, &'static str>) -> MockNode {
+        MockNode::start(move |method, _| match (method, &output) {
+            ("eth_call", Ok(output)) => Ok(Value::from(output.as_str())),
+            ("eth_call", Err(data)) => Err(revert_error(*data)),
+            _ => Ok(Value::Null),
+        })
+        .await
+        .unwrap()
+    }
+
+    fn client(url: &str) -> Arc<Provider<Http>> {
+        Arc::new(Provider::try_from(url).unwrap())
+    }
+
+    fn overrides(decimals: u8) -> HashMap<Address, u8> {
+        HashMap::from([(TOKEN, decimals)])
+    }
+
+    #[tokio::test]
+    async fn decimals_are_read_from_the_token() {
+        let node = node(Ok(format!("0x{:064x}", 6))).await;
+        let decimals = token_decimals(client(node.url()), TOKEN, &overrides(9)).await;
+        assert_eq!(decimals.unwrap(), 6);
+    }
+
+    #[tokio::test]
+    async fn a_reverting_decimals_falls_back_to_the_override() {
+        let node = node(Err("0x")).await;
+        let decimals = token_decimals(client(node.url()), TOKEN, &overrides(6)).await;
+        assert_eq!(decimals.unwrap(), 6);
+        assert_eq!(node.count("eth_call"), 1);
+
+        let decimals = token_decimals(client(node.url()), TOKEN, &HashMap::new()).await;
+        assert_eq!(decimals.unwrap(), DEFAULT_DECIMALS);
+    }
+
+    #[tokio::test]
+    async fn undecodable_output_falls_back_to_the_override() {
+        // What a call to an address without code returns
+        let node = node(Ok("0x".into())).await;
+        let decimals = token_decimals(client(node.url()), TOKEN, &overrides(6)).await;
+        assert_eq!(decimals.unwrap(), 6);
+
+        // A value too large for uint8
+        let node = self::node(Ok(format!("0x{:064x}", 256))).await;
+        let decimals = token_decimals(client(node.url()), TOKEN, &overrides(6)).await;
+        assert_eq!(decimals.unwrap(), 6);
+    }
+
+    #[tokio::test]
+    async fn transport_errors_are_returned_rather_than_overridden() {
+        let dead = dead_endpoint().await.unwrap();
+        let decimals = token_decimals(client(&dead), TOKEN, &overrides(6)).await;
+        assert!(decimals.is_err());
+    }
+}
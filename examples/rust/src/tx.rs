@@ -0,0 +1,67 @@
+//! Transaction type selection.
+//!
+//! Some chains reject EIP-1559 transactions and others require them. Instead
+//! of letting ethers pick implicitly, the type is configured and, for
+//! `auto`, probed once on connect.
+
+use ethers::{providers::Middleware, types::transaction::eip2718::TypedTransaction};
+use serde::Deserialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxType {
+    Legacy,
+    Eip1559,
+    #[default]
+    Auto,
+}
+
+/// The transaction type actually used for sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolvedTxType {
+    Legacy,
+    Eip1559,
+}
+
+impl TxType {
+    /// Pick a concrete type. `Auto` asks the node for EIP-1559 fee data
+    /// (latest base fee and `eth_feeHistory`) and falls back to legacy if it
+    /// can't provide it.
+    pub async fn resolve<M: Middleware>(self, provider: &M) -> ResolvedTxType {
+        match self {
+            TxType::Legacy => ResolvedTxType::Legacy,
+            TxType::Eip1559 => ResolvedTxType::Eip1559,
+            TxType::Auto => match provider.estimate_eip1559_fees(None).await {
+                Ok(_) => ResolvedTxType::Eip1559,
+                Err(_) => ResolvedTxType::Legacy,
+            },
+        }
+    }
+}
+
+impl ResolvedTxType {
+    /// Rebuild `tx` as this transaction type, keeping its fields.
+    ///
+    /// Works on the `tx` of a contract call or a deployer alike.
+    pub fn apply(self, tx: &mut TypedTransaction) {
+        match self {
+            ResolvedTxType::Legacy if !matches!(tx, TypedTransaction::Legacy(_)) => {
+                *tx = TypedTransaction::Legacy(tx.clone().into());
+            }
+            ResolvedTxType::Eip1559 if !matches!(tx, TypedTransaction::Eip1559(_)) => {
+                *tx = TypedTransaction::Eip1559(tx.clone().into());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for ResolvedTxType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolvedTxType::Legacy => write!(f, "Legacy"),
+            ResolvedTxType::Eip1559 => write!(f, "EIP-1559"),
+        }
+    }
+}
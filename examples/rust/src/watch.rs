@@ -0,0 +1,24 @@
+//! Live streaming of flash loan events over a WebSocket subscription.
+
+use crate::events::{decode_log, flash_loan_topics, FlashLoanExecuted};
+use ethers::{
+    providers::{Middleware, Provider, PubsubClient},
+    types::{Address, Filter},
+};
+use eyre::Result;
+use futures::{Stream, StreamExt};
+
+/// Subscribe to flash loan logs emitted by `provider_addr`.
+///
+/// Events are yielded as they arrive. Logs undone by a reorg are delivered a
+/// second time with `removed` set, so consumers can retract them.
+pub async fn watch_flash_loans<P: PubsubClient>(
+    provider: &Provider<P>,
+    provider_addr: Address,
+) -> Result<impl Stream<Item = FlashLoanExecuted> + '_> {
+    let filter = Filter::new()
+        .address(provider_addr)
+        .topic0(flash_loan_topics());
+    let stream = provider.subscribe_logs(&filter).await?;
+    Ok(stream.filter_map(|log| async move { decode_log(&log) }))
+}
@@ -0,0 +1,66 @@
+//! Balance snapshots across several freshly deployed test tokens.
+
+mod common;
+
+use common::{deploy_test_token, mint, test_token_artifact, Chain};
+use ethers::{providers::Middleware, types::U256, utils::parse_ether};
+use flashloan_example::{abi::IERC20, balances::snapshot_balances, expect::Expect};
+
+#[tokio::test]
+async fn snapshot_captures_every_minted_balance() {
+    let Some(chain) = Chain::start() else {
+        return;
+    };
+    let Some(artifact) = test_token_artifact() else {
+        return;
+    };
+    let client = chain.client(0, 0).await;
+    let holder = chain.account(4);
+
+    chain
+        .isolated(|| async {
+            let mut tokens = Vec::new();
+            let mut minted = Vec::new();
+            for (i, symbol) in ["AAA", "BBB", "CCC", "DDD", "EEE"].iter().enumerate() {
+                let token = deploy_test_token(&client, &artifact, symbol, 1_000).await?;
+                assert!(!client.provider.get_code(token, None).await?.is_empty());
+
+                // The constructor mints the supply to the deployer
+                let erc20 = IERC20::new(token, client.owner.clone());
+                assert_eq!(erc20.symbol().call().await?, *symbol);
+                Expect::balance(token, chain.account(0))
+                    .exactly(parse_ether(1_000)?)
+                    .check(client.owner.clone())
+                    .await?;
+
+                // The last token is never minted to the holder
+                let amount = match i {
+                    4 => U256::zero(),
+                    _ => parse_ether(7 * (i as u64 + 1))?,
+                };
+                if !amount.is_zero() {
+                    mint(&client, &artifact, token, holder, amount).await?;
+                }
+                tokens.push(token);
+                minted.push(amount);
+            }
+
+            let balances = snapshot_balances(client.owner.clone(), holder, &tokens).await?;
+            assert_eq!(balances.len(), tokens.len());
+            for (token, amount) in tokens.iter().zip(&minted) {
+                Expect::balance(*token, holder)
+                    .exactly(*amount)
+                    .verify(balances[token])?;
+            }
+
+            let owner = snapshot_balances(client.owner.clone(), chain.account(0), &tokens).await?;
+            for token in &tokens {
+                Expect::balance(*token, chain.account(0))
+                    .exactly(parse_ether(1_000)?)
+                    .verify(owner[token])?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+}
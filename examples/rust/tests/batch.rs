@@ -0,0 +1,118 @@
+//! Concurrent, bounded reads for a batch of loans, returned in job order.
+
+mod common;
+
+use common::{call_data, selector, word, KEY};
+use ethers::types::{Address, U256};
+use flashloan_example::{
+    batch::{prefetch_batch, Job, PREFETCH_CONCURRENCY},
+    secret::Redacted,
+    testing::{revert_error, MockNode},
+    tx::TxType,
+    Config, FlashLoanClient,
+};
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+
+// More jobs than may be in flight at once, so the bound is exercised
+const JOBS: u64 = PREFETCH_CONCURRENCY as u64 + 4;
+// Reads a job makes at once: symbol, decimals and the pool's balance
+const READS_PER_JOB: usize = 3;
+
+fn answer_call(params: &Value) -> Result<Value, ethers::providers::JsonRpcError> {
+    let data = call_data(params);
+    match &data[..data.len().min(10)] {
+        s if s == selector("decimals()") => Ok(word(6)),
+        s if s == selector("balanceOf(address)") => Ok(word(U256::exp10(30))),
+        s if s == selector("flashLoanFeeBps()") => Ok(word(9)),
+        // symbol() and flashFee() revert, as they do on minimal tokens and
+        // on providers older than ERC-3156
+        _ => Err(revert_error("0x")),
+    }
+}
+
+// Calls to the token of job `i` wait longer the earlier the job, so
+// answers come back in reverse job order
+fn delay(method: &str, params: &Value) -> Duration {
+    let job = params[0]["to"]
+        .as_str()
+        .and_then(|to| to.parse::<Address>().ok())
+        .map(|to| to.to_low_u64_be())
+        .filter(|job| (1..=JOBS).contains(job));
+    match (method, job) {
+        ("eth_call", Some(job)) => Duration::from_millis(20 * (JOBS + 1 - job)),
+        _ => Duration::from_millis(20),
+    }
+}
+
+async fn node() -> MockNode {
+    MockNode::start_with_delays(delay, |method, params| match method {
+        "eth_chainId" => Ok(Value::from("0x2612")),
+        "eth_call" => answer_call(params),
+        _ => Ok(Value::Null),
+    })
+    .await
+    .unwrap()
+}
+
+async fn client(node: &MockNode) -> FlashLoanClient {
+    let mut config = Config::default();
+    config.rpc.url = node.url().to_string();
+    config.owner_key = Some(Redacted::new(KEY.to_string()));
+    config.tx_type = TxType::Legacy;
+    FlashLoanClient::connect(&config).await.unwrap()
+}
+
+fn jobs() -> Vec<Job> {
+    (1..=JOBS)
+        .map(|i| Job {
+            token: Address::from_low_u64_be(i),
+            amount: i.to_string(),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn results_come_back_in_job_order_when_answers_do_not() {
+    let node = node().await;
+    let client = client(&node).await;
+    let jobs = jobs();
+
+    let contexts = prefetch_batch(&client, Address::repeat_byte(0xfe), &jobs, &HashMap::new())
+        .await
+        .unwrap();
+
+    // The loan is sent with the tokens and amounts in this order
+    let tokens: Vec<Address> = contexts.iter().map(|job| job.token).collect();
+    assert_eq!(tokens, jobs.iter().map(|job| job.token).collect::<Vec<_>>());
+    for (job, context) in jobs.iter().zip(&contexts) {
+        assert_eq!(context.amount.to_string(), format!("{}.000000", job.amount));
+    }
+    // The fee function was probed once, not once per job
+    assert_eq!(node.count("eth_call") as u64, 2 + 4 * JOBS);
+    assert!(contexts.iter().all(|job| job.fee.bps == 9));
+}
+
+#[tokio::test]
+async fn reads_overlap_up_to_the_concurrency_cap() {
+    let node = node().await;
+    let client = client(&node).await;
+
+    prefetch_batch(
+        &client,
+        Address::repeat_byte(0xfe),
+        &jobs(),
+        &HashMap::new(),
+    )
+    .await
+    .unwrap();
+
+    // More than one job's reads at once, but never more jobs than the cap
+    let peak = node.max_in_flight();
+    assert!(peak > READS_PER_JOB, "at most {} reads in flight", peak);
+    assert!(
+        peak <= READS_PER_JOB * PREFETCH_CONCURRENCY,
+        "{} reads in flight",
+        peak
+    );
+}
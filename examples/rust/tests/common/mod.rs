@@ -0,0 +1,164 @@
+//! Fixtures shared by the integration tests.
+//!
+//! Tests that need a chain run against a local Anvil node started with the
+//! Plasma testnet chain id, so `FlashLoanClient::connect` accepts it. Tests
+//! that change state run inside [`Chain::isolated`], which reverts to a
+//! snapshot afterwards. When `anvil` isn't installed the test prints a note
+//! and passes without running.
+//!
+//! Tests that only need a few RPC answers use `testing::MockNode`, with the
+//! helpers at the end of this module to match calls and encode results.
+
+#![allow(dead_code)]
+
+use ethers::{
+    abi::Token,
+    contract::Contract,
+    providers::{Http, Provider},
+    types::{Address, U256},
+    utils::{id, Anvil, AnvilInstance},
+};
+use eyre::Result;
+use flashloan_example::{
+    deployment::{deploy_contract, Artifact, ArtifactLoader, PROJECT_ROOT},
+    secret::Redacted,
+    send::TxBuilder,
+    testing::with_snapshot,
+    Config, FlashLoanClient, Network,
+};
+use serde_json::Value;
+use std::{future::Future, process::Command};
+
+/// Anvil's first prefunded account; never used on a real chain.
+pub const KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+/// A local Anvil node and a provider connected to it.
+pub struct Chain {
+    anvil: AnvilInstance,
+    pub provider: Provider<Http>,
+}
+
+impl Chain {
+    /// Spawn Anvil, or `None` when the binary isn't on `PATH`.
+    pub fn start() -> Option<Self> {
+        if Command::new("anvil").arg("--version").output().is_err() {
+            eprintln!("skipping: anvil is not installed");
+            return None;
+        }
+        let anvil = Anvil::new()
+            .chain_id(Network::PlasmaTestnet.chain_id())
+            .spawn();
+        let provider = Provider::try_from(anvil.endpoint()).expect("anvil endpoint");
+        Some(Self { anvil, provider })
+    }
+
+    /// Address of the `index`th prefunded account.
+    pub fn account(&self, index: usize) -> Address {
+        self.anvil.addresses()[index]
+    }
+
+    /// Private key of the `index`th prefunded account, as `capacitor.toml`
+    /// takes it.
+    pub fn key(&self, index: usize) -> Redacted<String> {
+        let bytes = self.anvil.keys()[index].to_bytes();
+        Redacted::new(format!("0x{}", hex::encode(bytes)))
+    }
+
+    /// Config for this node with account `owner` as the owner and account
+    /// `funder` as the funder.
+    pub fn config(&self, owner: usize, funder: usize) -> Config {
+        let mut config = Config::default();
+        config.rpc.url = self.anvil.endpoint();
+        config.owner_key = Some(self.key(owner));
+        config.funder_key = Some(self.key(funder));
+        config.poll_interval = 0.1;
+        config
+    }
+
+    pub async fn client(&self, owner: usize, funder: usize) -> FlashLoanClient {
+        FlashLoanClient::connect(&self.config(owner, funder))
+            .await
+            .expect("connect to anvil")
+    }
+
+    /// Run `f` in a snapshot so whatever it changes is rolled back.
+    pub async fn isolated<F, Fut, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        with_snapshot(&self.provider, f).await
+    }
+}
+
+/// The compiled `TestToken`, or `None` when the contracts haven't been
+/// built with `npx hardhat compile` or `forge build`.
+pub fn test_token_artifact() -> Option<Artifact> {
+    match ArtifactLoader::new(PROJECT_ROOT).load("TestToken") {
+        Ok(artifact) => Some(artifact),
+        Err(e) => {
+            eprintln!("skipping: {}", e);
+            None
+        }
+    }
+}
+
+/// Deploy a `TestToken` from the owner, which receives `supply` whole tokens.
+pub async fn deploy_test_token(
+    client: &FlashLoanClient,
+    artifact: &Artifact,
+    symbol: &str,
+    supply: u64,
+) -> Result<Address> {
+    let args = vec![
+        Token::String(format!("Test {}", symbol)),
+        Token::String(symbol.to_string()),
+        Token::Uint(U256::from(supply)),
+    ];
+    deploy_contract(
+        client,
+        artifact.abi.clone(),
+        artifact.bytecode.clone(),
+        args,
+    )
+    .await
+}
+
+/// Mint `amount` base units of `token` to `to`.
+pub async fn mint(
+    client: &FlashLoanClient,
+    artifact: &Artifact,
+    token: Address,
+    to: Address,
+    amount: U256,
+) -> Result<()> {
+    let contract = Contract::new(token, artifact.abi.clone(), client.owner.clone());
+    let call = contract.method::<_, ()>("mint", (to, amount))?;
+    TxBuilder::call(client, &client.owner, call)
+        .send_and_confirm()
+        .await?;
+    Ok(())
+}
+
+/// The call data of an `eth_call`, whichever field the client sent it in.
+pub fn call_data(params: &Value) -> &str {
+    params[0]["data"]
+        .as_str()
+        .or_else(|| params[0]["input"].as_str())
+        .unwrap_or_default()
+}
+
+/// The 4-byte selector `call_data` starts with for `signature`, as hex.
+pub fn selector(signature: &str) -> String {
+    format!("0x{}", hex::encode(&id(signature)[..4]))
+}
+
+/// A single `uint256` result.
+pub fn word(value: impl Into<U256>) -> Value {
+    Value::from(format!("0x{:064x}", value.into()))
+}
+
+/// ABI-encoded output, as a node returns it.
+pub fn hex_value(bytes: Vec<u8>) -> Value {
+    Value::from(format!("0x{}", hex::encode(bytes)))
+}
@@ -0,0 +1,180 @@
+//! Failover between a dead and a live endpoint.
+
+use ethers::{
+    providers::{Http, JsonRpcClient, Middleware, RpcError},
+    types::U64,
+};
+use flashloan_example::{
+    client::{failover, read_only},
+    failover::FailoverProvider,
+    testing::{dead_endpoint, revert_error, MockNode},
+    CapacitorError, Config,
+};
+use serde_json::Value;
+use std::{str::FromStr, time::Duration};
+
+async fn live_node() -> MockNode {
+    MockNode::start(|method, _| match method {
+        "eth_blockNumber" => Ok(Value::from("0x2a")),
+        "eth_call" => Err(revert_error("0x")),
+        _ => Ok(Value::Null),
+    })
+    .await
+    .unwrap()
+}
+
+fn endpoints(urls: &[&str]) -> FailoverProvider<Http> {
+    FailoverProvider::new(
+        urls.iter()
+            .map(|url| (url.to_string(), Http::from_str(url).unwrap()))
+            .collect(),
+    )
+}
+
+#[tokio::test]
+async fn a_dead_endpoint_fails_over_to_the_next() {
+    let dead = dead_endpoint().await.unwrap();
+    let live = live_node().await;
+    let provider = endpoints(&[&dead, live.url()]);
+
+    let block: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(block, U64::from(42));
+    assert_eq!(live.count("eth_blockNumber"), 1);
+
+    let stats = provider.endpoint_stats();
+    assert_eq!(stats[0].failures, 1);
+    assert!(!stats[0].healthy);
+    assert_eq!(stats[1].failures, 0);
+    assert!(stats[1].healthy);
+}
+
+#[tokio::test]
+async fn the_live_endpoint_is_remembered() {
+    let dead = dead_endpoint().await.unwrap();
+    let live = live_node().await;
+    let provider = endpoints(&[&dead, live.url()]);
+
+    for _ in 0..3 {
+        let _: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+    }
+    assert_eq!(provider.current(), live.url());
+    // The dead endpoint was tried once, then ranked last
+    assert_eq!(provider.endpoint_stats()[0].requests, 1);
+    assert_eq!(live.count("eth_blockNumber"), 3);
+}
+
+#[tokio::test]
+async fn rpc_errors_do_not_fail_over() {
+    let first = live_node().await;
+    let second = live_node().await;
+    let provider = endpoints(&[first.url(), second.url()]);
+
+    let err = provider
+        .request::<_, Value>("eth_call", ())
+        .await
+        .unwrap_err();
+    assert_eq!(err.as_error_response().unwrap().code, 3);
+    assert_eq!(first.count("eth_call"), 1);
+    assert!(second.calls().is_empty());
+    assert!(provider.endpoint_stats()[0].healthy);
+}
+
+#[tokio::test]
+async fn every_endpoint_down_is_an_error() {
+    let provider = endpoints(&[
+        &dead_endpoint().await.unwrap(),
+        &dead_endpoint().await.unwrap(),
+    ]);
+    let result: Result<U64, _> = provider.request("eth_blockNumber", ()).await;
+    assert!(result.is_err());
+    assert!(provider.endpoint_stats().iter().all(|stat| !stat.healthy));
+}
+
+#[tokio::test]
+async fn rpc_urls_in_the_config_fail_over() {
+    let dead = dead_endpoint().await.unwrap();
+    let live = live_node().await;
+    let config: Config =
+        toml::from_str(&format!("rpc_urls = [\"{}\", \"{}\"]", dead, live.url())).unwrap();
+    assert_eq!(config.rpc_endpoints().unwrap(), [dead.as_str(), live.url()]);
+
+    let provider = read_only(&config).unwrap();
+    assert_eq!(provider.get_block_number().await.unwrap(), U64::from(42));
+    let stats = failover(&provider).unwrap().endpoint_stats();
+    assert_eq!(stats[0].url, dead);
+    assert_eq!(stats[0].failures, 1);
+}
+
+#[test]
+fn rpc_urls_and_the_rpc_table_list_are_exclusive() {
+    let config: Config =
+        toml::from_str("rpc_urls = [\"http://a\", \"http://b\"]\n[rpc]\nurl = \"http://c\"")
+            .unwrap();
+    assert_eq!(config.rpc_endpoints().unwrap(), ["http://a", "http://b"]);
+
+    let config: Config = toml::from_str("[rpc]\nurls = [\"http://d\"]").unwrap();
+    assert_eq!(config.rpc_endpoints().unwrap(), ["http://d"]);
+
+    let config: Config =
+        toml::from_str("rpc_urls = [\"http://a\"]\n[rpc]\nurls = [\"http://d\"]").unwrap();
+    let err = config.rpc_endpoints().unwrap_err();
+    assert!(matches!(err, CapacitorError::Config(_)), "{}", err);
+    assert_eq!(err.exit_code(), 2);
+}
+
+async fn node_with_latency(millis: u64) -> MockNode {
+    MockNode::start_with_latency(Duration::from_millis(millis), |_, _| {
+        Ok(Value::from("0x2a"))
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn the_fastest_endpoint_ends_up_first() {
+    let slow = node_with_latency(60).await;
+    let fast = node_with_latency(5).await;
+    // Configured slowest first, so only the latency ranking can reorder them
+    let provider = endpoints(&[slow.url(), fast.url()]);
+    assert_eq!(provider.current(), slow.url());
+
+    for _ in 0..10 {
+        let _: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+    }
+
+    assert_eq!(provider.current(), fast.url());
+    let stats = provider.endpoint_stats();
+    assert!(stats[1].ema_latency < stats[0].ema_latency);
+    // One sample of the slow endpoint was enough to rank it behind
+    assert_eq!(slow.count("eth_blockNumber"), 1);
+    assert_eq!(fast.count("eth_blockNumber"), 9);
+}
+
+#[tokio::test]
+async fn probing_measures_every_endpoint() {
+    let slow = node_with_latency(60).await;
+    let fast = node_with_latency(5).await;
+    let provider = endpoints(&[slow.url(), fast.url()]);
+
+    provider.probe().await;
+
+    let stats = provider.endpoint_stats();
+    assert!(stats.iter().all(|stat| stat.requests == 1 && stat.healthy));
+    assert!(stats[0].ema_latency.unwrap() >= Duration::from_millis(60));
+    assert_eq!(provider.current(), fast.url());
+}
+
+#[tokio::test]
+async fn the_fastest_endpoint_is_passed_over_once_it_fails() {
+    let first = node_with_latency(5).await;
+    let second = node_with_latency(30).await;
+    let provider = endpoints(&[first.url(), second.url()]);
+    provider.probe().await;
+    assert_eq!(provider.current(), first.url());
+
+    // Take the fast node down; its latency no longer keeps it first
+    drop(first);
+    let _: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(provider.current(), second.url());
+    assert!(!provider.endpoint_stats()[0].healthy);
+}
@@ -0,0 +1,151 @@
+//! `max_loan_amount` stops a loan in preflight, before anything is sent.
+
+mod common;
+
+use common::{call_data, hex_value, selector, KEY};
+use ethers::{
+    abi::{encode, Token},
+    providers::{Http, Provider},
+    signers::{LocalWallet, Signer},
+    types::{Address, I256, U256},
+};
+use flashloan_example::{
+    expect::Expect,
+    testing::{revert_error, MockNode},
+    Config,
+};
+use serde_json::Value;
+use std::{env, fs, path::PathBuf, process::Output, sync::Arc};
+use tokio::process::Command;
+
+const TESTER: Address = Address::repeat_byte(0x77);
+const SENDS: [&str; 2] = ["eth_sendRawTransaction", "eth_sendTransaction"];
+
+/// A testnet node where the tester is wired up correctly and the token has
+/// 6 decimals and plenty of liquidity.
+async fn node() -> MockNode {
+    let owner = KEY.parse::<LocalWallet>().unwrap().address();
+    let provider = Config::default().flash_loan_provider;
+    MockNode::start(move |method, params| match method {
+        "eth_chainId" => Ok(Value::from("0x2612")),
+        "eth_getCode" => Ok(Value::from("0x6000")),
+        "eth_call" => {
+            let data = call_data(params);
+            let output = match &data[..data.len().min(10)] {
+                s if s == selector("symbol()") => encode(&[Token::String("TUSDT".into())]),
+                s if s == selector("decimals()") => encode(&[Token::Uint(6.into())]),
+                s if s == selector("balanceOf(address)") => encode(&[Token::Uint(U256::exp10(24))]),
+                s if s == selector("owner()") => encode(&[Token::Address(owner)]),
+                s if s == selector("flashLoanProvider()") => encode(&[Token::Address(provider)]),
+                _ => return Err(revert_error("0x")),
+            };
+            Ok(hex_value(output))
+        }
+        _ => Ok(Value::Null),
+    })
+    .await
+    .unwrap()
+}
+
+/// Run `bin` with `args` against `node`, with a 10-token `max_loan_amount`.
+async fn run(node: &MockNode, name: &str, bin: &str, args: &[&str]) -> Output {
+    let dir = env::temp_dir().join(format!("capacitor-cap-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    let config: PathBuf = dir.join("capacitor.toml");
+    fs::write(
+        &config,
+        format!(
+            "owner_key = \"{}\"\ndeployed_contract = \"{:?}\"\nmax_loan_amount = 10\n\
+             tx_type = \"legacy\"\n[rpc]\nurl = \"{}\"\n",
+            KEY,
+            TESTER,
+            node.url()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(bin)
+        .args(args)
+        .current_dir(&dir)
+        .env("CAPACITOR_CONFIG", &config)
+        .env_remove("OWNER_KEY")
+        .env_remove("PRIVATE_KEY")
+        .env_remove("FUNDER_KEY")
+        .env_remove("DEPLOYED_CONTRACT")
+        .output()
+        .await
+        .unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    output
+}
+
+/// Run `bin` as [`run`] does and check the cap stopped it: exit code 3,
+/// nothing sent and the tester's token balance untouched.
+async fn assert_stopped_by_the_cap(node: &MockNode, name: &str, bin: &str, args: &[&str]) {
+    let provider = Arc::new(Provider::<Http>::try_from(node.url()).unwrap());
+    let balance = Expect::balance(Config::default().token, TESTER)
+        .capture(provider.clone())
+        .await
+        .unwrap();
+
+    let output = run(node, name, bin, args).await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{}\n{}", stdout, stderr);
+    assert!(stdout.contains("exceeds max_loan_amount"), "{}", stdout);
+    for send in SENDS {
+        assert_eq!(node.count(send), 0, "{} before the cap check", send);
+    }
+    balance
+        .changed_by(I256::zero())
+        .check(provider)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn execute_rejects_a_loan_over_the_cap_before_sending() {
+    let node = node().await;
+    assert_stopped_by_the_cap(
+        &node,
+        "execute",
+        env!("CARGO_BIN_EXE_execute"),
+        &["--amount", "100", "--yes"],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn flash_multi_rejects_a_loan_over_the_cap_before_sending() {
+    let node = node().await;
+    assert_stopped_by_the_cap(
+        &node,
+        "flash-multi",
+        env!("CARGO_BIN_EXE_capacitor"),
+        &[
+            "--yes",
+            "flash-multi",
+            "--loan",
+            "TUSDT=5",
+            "--loan",
+            "TUSDT=100",
+        ],
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn a_loan_at_the_cap_gets_past_the_check() {
+    let node = node().await;
+    let output = run(
+        &node,
+        "at-cap",
+        env!("CARGO_BIN_EXE_execute"),
+        &["--amount", "10", "--yes"],
+    )
+    .await;
+    // The run fails later, where the mock stops answering, but not on the cap
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("exceeds max_loan_amount"), "{}", stdout);
+    assert!(stdout.contains("Pool Liquidity"), "{}", stdout);
+}
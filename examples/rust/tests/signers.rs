@@ -0,0 +1,59 @@
+//! Owner and funder roles on separate Anvil accounts.
+
+mod common;
+
+use common::Chain;
+use ethers::{
+    providers::Middleware,
+    types::{TransactionRequest, U256},
+};
+use flashloan_example::{send::TxBuilder, FlashLoanClient};
+
+#[tokio::test]
+async fn sends_are_signed_by_the_role_they_are_routed_to() {
+    let Some(chain) = Chain::start() else {
+        return;
+    };
+    let client = chain.client(0, 1).await;
+    assert_eq!(client.owner_address(), chain.account(0));
+    assert_eq!(client.funder_address(), chain.account(1));
+    assert!(client.has_separate_funder());
+
+    chain
+        .isolated(|| async {
+            let payment = TransactionRequest::new().to(chain.account(2)).value(1);
+            let funded = TxBuilder::new(&client, &client.funder, payment.clone())
+                .send_and_confirm()
+                .await?;
+            assert_eq!(funded.from, chain.account(1));
+
+            let initiated = TxBuilder::new(&client, &client.owner, payment)
+                .send_and_confirm()
+                .await?;
+            assert_eq!(initiated.from, chain.account(0));
+
+            // Each account sent exactly one transaction
+            for index in [0, 1] {
+                let nonce = client
+                    .provider
+                    .get_transaction_count(chain.account(index), None)
+                    .await?;
+                assert_eq!(nonce, U256::one());
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn funder_defaults_to_the_owner() {
+    let Some(chain) = Chain::start() else {
+        return;
+    };
+    let mut config = chain.config(0, 0);
+    config.funder_key = None;
+    let client = FlashLoanClient::connect(&config).await.unwrap();
+    assert_eq!(client.funder_address(), chain.account(0));
+    assert!(!client.has_separate_funder());
+}
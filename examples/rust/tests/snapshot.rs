@@ -0,0 +1,95 @@
+//! Snapshot and revert around a run.
+
+mod common;
+
+use common::Chain;
+use ethers::{
+    providers::Middleware,
+    types::{TransactionRequest, U256},
+};
+use eyre::eyre;
+use flashloan_example::send::TxBuilder;
+
+#[tokio::test]
+async fn state_changed_inside_a_snapshot_is_rolled_back() {
+    let Some(chain) = Chain::start() else {
+        return;
+    };
+    let client = chain.client(0, 0).await;
+    let recipient = chain.account(3);
+    let before = chain.provider.get_balance(recipient, None).await.unwrap();
+
+    let during = chain
+        .isolated(|| async {
+            let payment = TransactionRequest::new().to(recipient).value(1_000);
+            TxBuilder::new(&client, &client.owner, payment)
+                .send_and_confirm()
+                .await?;
+            Ok(chain.provider.get_balance(recipient, None).await?)
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(during, before + U256::from(1_000));
+    let after = chain.provider.get_balance(recipient, None).await.unwrap();
+    assert_eq!(after, before);
+}
+
+#[tokio::test]
+async fn a_failed_run_is_rolled_back_too() {
+    let Some(chain) = Chain::start() else {
+        return;
+    };
+    let client = chain.client(0, 0).await;
+    let owner = chain.account(0);
+    let nonce = chain
+        .provider
+        .get_transaction_count(owner, None)
+        .await
+        .unwrap();
+
+    let result: eyre::Result<()> = chain
+        .isolated(|| async {
+            let payment = TransactionRequest::new().to(chain.account(3)).value(1);
+            TxBuilder::new(&client, &client.owner, payment)
+                .send_and_confirm()
+                .await?;
+            Err(eyre!("run failed after sending"))
+        })
+        .await;
+
+    assert_eq!(result.unwrap_err().to_string(), "run failed after sending");
+    let after = chain
+        .provider
+        .get_transaction_count(owner, None)
+        .await
+        .unwrap();
+    assert_eq!(after, nonce);
+}
+
+#[tokio::test]
+async fn consecutive_runs_see_the_same_starting_state() {
+    let Some(chain) = Chain::start() else {
+        return;
+    };
+    let client = chain.client(0, 0).await;
+
+    for _ in 0..2 {
+        let nonce = chain
+            .isolated(|| async {
+                let payment = TransactionRequest::new().to(chain.account(3)).value(1);
+                let receipt = TxBuilder::new(&client, &client.owner, payment)
+                    .send_and_confirm()
+                    .await?;
+                let tx = chain
+                    .provider
+                    .get_transaction(receipt.transaction_hash)
+                    .await?
+                    .ok_or_else(|| eyre!("mined transaction not found"))?;
+                Ok(tx.nonce)
+            })
+            .await
+            .unwrap();
+        assert_eq!(nonce, U256::zero());
+    }
+}